@@ -1,11 +1,25 @@
 use crate::error::ContractError;
-use crate::execute::buy_sell::exec_buy;
+use crate::execute::buy_sell::{exec_buy, exec_buy_exact_out};
+use crate::execute::claim_fees::exec_claim_fees;
 use crate::execute::cw20_receive::exec_cw20_receive;
+use crate::execute::fee_overrides::exec_set_fee_override;
+use crate::execute::holder_tiers::exec_set_holder_tiers;
 use crate::execute::on_balance_change::exec_on_balance_change;
+use crate::execute::volume_tiers::exec_set_volume_tiers;
 use crate::execute::{set_config::exec_set_config, Context};
 use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
 use crate::query::account::query_account;
+use crate::query::claimable_fees::query_claimable_fees;
+use crate::query::depth::query_depth;
+use crate::query::fee_overrides::query_fee_overrides;
+use crate::query::fee_preview::query_fee_preview;
 use crate::query::overview::query_overview;
+use crate::query::referrer::query_referrer;
+use crate::query::simulate::{
+    query_reverse_simulate_buy, query_reverse_simulate_sell, query_simulate_buy, query_simulate_sell,
+    query_simulate_steps,
+};
+use crate::query::tranches::query_tranches;
 use crate::query::{config::query_config, ReadonlyContext};
 use crate::state::{self, handle_cw20_instantiate_reply, CW20_INSTANTIATE_REPLY_ID};
 use cosmwasm_std::{entry_point, to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response};
@@ -38,6 +52,11 @@ pub fn execute(
         ExecuteMsg::OnBalanceChange(msg) => exec_on_balance_change(ctx, msg),
         ExecuteMsg::Receive(msg) => exec_cw20_receive(ctx, msg),
         ExecuteMsg::Buy(msg) => exec_buy(ctx, msg, None),
+        ExecuteMsg::BuyExactOut(msg) => exec_buy_exact_out(ctx, msg, None),
+        ExecuteMsg::ClaimFees {} => exec_claim_fees(ctx),
+        ExecuteMsg::SetFeeOverride(msg) => exec_set_fee_override(ctx, msg),
+        ExecuteMsg::SetHolderTiers(config) => exec_set_holder_tiers(ctx, config),
+        ExecuteMsg::SetVolumeTiers(tiers) => exec_set_volume_tiers(ctx, tiers),
     }
 }
 
@@ -64,6 +83,17 @@ pub fn query(
         QueryMsg::Config {} => to_json_binary(&query_config(ctx)?),
         QueryMsg::Overview {} => to_json_binary(&query_overview(ctx)?),
         QueryMsg::Account { address } => to_json_binary(&query_account(ctx, address)?),
+        QueryMsg::Referrer { address } => to_json_binary(&query_referrer(ctx, address)?),
+        QueryMsg::ClaimableFees { address } => to_json_binary(&query_claimable_fees(ctx, address)?),
+        QueryMsg::FeePreview { address } => to_json_binary(&query_fee_preview(ctx, address)?),
+        QueryMsg::FeeOverrides { start_after, limit } => to_json_binary(&query_fee_overrides(ctx, start_after, limit)?),
+        QueryMsg::Depth { levels } => to_json_binary(&query_depth(ctx, levels)?),
+        QueryMsg::Tranches {} => to_json_binary(&query_tranches(ctx)?),
+        QueryMsg::SimulateBuy { in_amount } => to_json_binary(&query_simulate_buy(ctx, in_amount)?),
+        QueryMsg::SimulateSell { in_amount } => to_json_binary(&query_simulate_sell(ctx, in_amount)?),
+        QueryMsg::ReverseSimulateBuy { base_out } => to_json_binary(&query_reverse_simulate_buy(ctx, base_out)?),
+        QueryMsg::ReverseSimulateSell { quote_out } => to_json_binary(&query_reverse_simulate_sell(ctx, quote_out)?),
+        QueryMsg::SimulateSteps { steps } => to_json_binary(&query_simulate_steps(ctx, steps)?),
     }?;
     Ok(result)
 }
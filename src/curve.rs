@@ -1,35 +1,268 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Uint128, Uint256};
+use cosmwasm_std::{Decimal256, StdError, Uint128, Uint256};
 
 use crate::{
     error::ContractError,
-    math::{add_u128, div_u256, mul_ratio_u128, sub_u128},
+    math::{
+        add_dec256, add_u128, add_u256, div_ceil_u256, div_dec256, div_u256, exp_dec256, from_ratio_dec256, mul_dec256,
+        mul_ratio_u256, mul_u256, pow_dec256, sub_dec256, sub_u128, sub_u256, to_u128,
+    },
 };
 
+/// Largest exponent accepted by the power-law curve
+pub const MAX_POWER_LAW_EXPONENT: u8 = 8;
+
+/// Largest number of tranches accepted by the stepped curve
+pub const MAX_TRANCHES: usize = 32;
+
+/// Largest growth rate accepted by the exponential curve. At this rate, the
+/// end price is about e^40 times the start price.
+pub const MAX_EXPONENTIAL_GROWTH: u64 = 40;
+
+/// Shape of the bonding curve. Except for the constant product curve, which is
+/// priced by its reserves, each shape prices the base token as a function of x,
+/// the fraction of the base supply sold so far. Prices are given in whole quote
+/// tokens per whole base token.
+#[cw_serde]
+pub enum CurveKind {
+    /// Classic x*y=k AMM over the base and quote reserves
+    ConstantProduct {},
+    /// Price rises linearly from `start_price` to `end_price`
+    Linear {
+        start_price: Decimal256,
+        end_price: Decimal256,
+    },
+    /// Price is `start_price * e^(growth * x)`
+    Exponential {
+        start_price: Decimal256,
+        growth: Decimal256,
+    },
+    /// Price is `start_price + (end_price - start_price) * x^exponent`
+    PowerLaw {
+        start_price: Decimal256,
+        end_price: Decimal256,
+        exponent: u8,
+    },
+    /// Price is fixed within each tranche, which cover consecutive slices of
+    /// the base supply in order
+    Stepped { tranches: Vec<Tranche> },
+}
+
+#[cw_serde]
+pub struct Tranche {
+    /// Percent of the base supply sold in the tranche, scaled by 1e6
+    pub supply_pct: Uint128,
+    /// Price of every base token in the tranche
+    pub price: Decimal256,
+}
+
+impl CurveKind {
+    pub fn validate(&self) -> Result<(), ContractError> {
+        let error = |reason: &str| {
+            Err(ContractError::ValidationError {
+                reason: reason.to_owned(),
+            })
+        };
+        match self {
+            Self::ConstantProduct {} => {},
+            Self::Linear { start_price, end_price } => {
+                if start_price.is_zero() || end_price < start_price {
+                    return error("linear curve needs 0 < start_price <= end_price");
+                }
+            },
+            Self::Exponential { start_price, growth } => {
+                if start_price.is_zero() {
+                    return error("exponential curve needs a nonzero start_price");
+                }
+                if growth.is_zero() || *growth > Decimal256::from_ratio(MAX_EXPONENTIAL_GROWTH, 1u128) {
+                    return error("exponential curve growth out of range");
+                }
+            },
+            Self::PowerLaw {
+                start_price,
+                end_price,
+                exponent,
+            } => {
+                if start_price.is_zero() || end_price < start_price {
+                    return error("power-law curve needs 0 < start_price <= end_price");
+                }
+                if *exponent == 0 || *exponent > MAX_POWER_LAW_EXPONENT {
+                    return error("power-law curve exponent out of range");
+                }
+            },
+            Self::Stepped { tranches } => {
+                if tranches.is_empty() || tranches.len() > MAX_TRANCHES {
+                    return error("stepped curve tranche count out of range");
+                }
+                let mut total_pct = Uint128::zero();
+                for tranche in tranches.iter() {
+                    if tranche.supply_pct.is_zero() || tranche.price.is_zero() {
+                        return error("stepped curve tranches need a nonzero supply_pct and price");
+                    }
+                    total_pct = add_u128(total_pct, tranche.supply_pct)?;
+                }
+                if total_pct != Uint128::new(1_000_000) {
+                    return error("stepped curve tranches must cover the whole supply");
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+/// Launch targets from which the virtual reserves of a constant product curve
+/// are derived, as an alternative to giving the reserves directly.
+#[cw_serde]
+pub struct CurveTargets {
+    /// Price of the first base token, in whole quote tokens per whole base token
+    pub start_price: Decimal256,
+    /// Value of the full base supply at the graduation price, in raw quote units
+    pub graduation_market_cap: Uint256,
+    /// Percent of the base supply sold by graduation, scaled by 1e6
+    pub graduation_supply_pct: Uint128,
+}
+
+impl CurveTargets {
+    /// Derives the virtual base and quote reserves along with the sellable
+    /// supply, such that the curve starts at the start price and reaches the
+    /// graduation market cap once the sellable supply is sold. With virtual
+    /// reserves B and Q and sellable supply s, the price rises by a factor of
+    /// r = (B / (B - s))^2, so B = s * sqrt(r) / (sqrt(r) - 1) and Q is B
+    /// valued at the start price. B must cover the whole base supply, since
+    /// any supply beyond it would sit outside the curve.
+    pub fn derive_reserves(
+        &self,
+        base_supply: Uint128,
+        base_decimals: u8,
+        quote_decimals: u8,
+    ) -> Result<(Uint256, Uint256, Uint256), ContractError> {
+        let error = |reason: &str| {
+            Err(ContractError::ValidationError {
+                reason: reason.to_owned(),
+            })
+        };
+        if self.start_price.is_zero() {
+            return error("curve targets need a nonzero start_price");
+        }
+        if self.graduation_supply_pct.is_zero() || self.graduation_supply_pct > Uint128::new(1_000_000) {
+            return error("graduation supply pct out of range");
+        }
+
+        let sellable_supply = mul_ratio_u256(base_supply, self.graduation_supply_pct, 1_000_000u128)?;
+        if sellable_supply.is_zero() {
+            return error("graduation supply rounds down to zero");
+        }
+
+        // Ratio of graduation price to start price
+        let price_ratio = from_ratio_dec256(
+            mul_u256(
+                mul_u256(self.graduation_market_cap, decimal_scale(base_decimals)?)?,
+                Decimal256::one().atomics(),
+            )?,
+            mul_u256(
+                mul_u256(base_supply, self.start_price.atomics())?,
+                decimal_scale(quote_decimals)?,
+            )?,
+        )?;
+        if price_ratio <= Decimal256::one() {
+            return error("graduation market cap must imply a price above the start price");
+        }
+
+        let sqrt_ratio = price_ratio.sqrt();
+        let base_reserve = mul_ratio_u256(
+            sellable_supply,
+            sqrt_ratio.atomics(),
+            sub_dec256(sqrt_ratio, Decimal256::one())?.atomics(),
+        )?;
+        if base_reserve < Uint256::from(base_supply) {
+            return error("graduation market cap is too high for the graduation supply pct to sell the whole base supply along the curve");
+        }
+        let quote_reserve = quote_value(base_reserve, self.start_price, base_decimals, quote_decimals)?;
+        if quote_reserve.is_zero() {
+            return error("start price implies an empty quote reserve");
+        }
+
+        Ok((base_reserve, quote_reserve, sellable_supply))
+    }
+}
+
 #[cw_serde]
 pub struct Curve {
+    pub kind: CurveKind,
     pub k: Uint256,
-    pub base_reserve: Uint128,
+    /// Base reserve at instantiation, i.e. the full supply sold along the curve
+    pub base_supply: Uint256,
+    /// Max amount of the base supply that the curve will ever sell
+    pub sellable_supply: Uint256,
+    /// Base reserve, including its virtual portion
+    pub base_reserve: Uint256,
+    /// Portion of the base reserve that prices the curve without being backed
+    /// by any tokens held by the contract
+    pub base_reserve_virtual: Uint256,
     pub base_decimals: u8,
-    pub quote_reserve: Uint128,
+    /// Quote reserve, including its virtual portion
+    pub quote_reserve: Uint256,
+    /// Portion of the quote reserve that prices the curve without being backed
+    /// by any tokens held by the contract
+    pub quote_reserve_virtual: Uint256,
     pub quote_decimals: u8,
 }
 
 impl Curve {
+    /// Buys base with `in_amount` quote. If the buy would overshoot the
+    /// sellable supply, it's only filled up to the limit. Returns the base
+    /// amount bought along with any quote left unused by a partial fill.
     pub fn buy(
         &mut self,
         in_amount: Uint128,
         min_amount_out: Option<Uint128>,
-    ) -> Result<Uint128, ContractError> {
-        let (new_quote_reserve, new_base_reserve, out_amount) = {
-            let new_quote_reserve = add_u128(self.quote_reserve, in_amount)?;
-            let new_base_reserve = div_u256(self.k, new_quote_reserve)?.try_into().unwrap();
-            let out_amount = sub_u128(self.base_reserve, new_base_reserve)?;
-            (new_quote_reserve, new_base_reserve, out_amount)
+    ) -> Result<(Uint128, Uint128), ContractError> {
+        let remaining = self.calculate_base_sellable()?;
+        if remaining.is_zero() {
+            return Err(ContractError::InsufficientLiquidity {});
+        }
+
+        // Fill up to the sellable supply if the input suffices to reach it. A
+        // constant product curve can't sell its entire reserve, so the limit
+        // only applies to it when set below the full reserve.
+        if remaining < self.base_reserve || !matches!(self.kind, CurveKind::ConstantProduct {}) {
+            let remaining = to_u128(remaining)?;
+            let mut filled = self.clone();
+            let fill_in_amount = filled.buy_exact_out(remaining, None)?;
+            if fill_in_amount <= in_amount {
+                *self = filled;
+                if let Some(min_amount_out) = min_amount_out {
+                    if remaining < min_amount_out {
+                        return Err(ContractError::TooMuchSlippage {});
+                    }
+                }
+                return Ok((remaining, sub_u128(in_amount, fill_in_amount)?));
+            }
+        }
+
+        let (new_quote_reserve, new_base_reserve, out_amount) = match self.kind {
+            CurveKind::ConstantProduct {} => {
+                let new_quote_reserve = add_u256(self.quote_reserve, in_amount)?;
+                let new_base_reserve = div_ceil_u256(self.k, new_quote_reserve)?;
+                let out_amount = to_u128(sub_u256(self.base_reserve, new_base_reserve)?)?;
+                (new_quote_reserve, new_base_reserve, out_amount)
+            },
+            _ => {
+                let new_quote_reserve = add_u256(self.quote_reserve, in_amount)?;
+                let sold = self.calculate_base_sold()?;
+                let target = add_u256(self.integrate(sold)?, in_amount)?;
+                let out_amount = to_u128(sub_u256(
+                    self.solve_base_sold(sold, self.sellable_supply, target)?,
+                    sold,
+                )?)?;
+                let new_base_reserve = sub_u256(self.base_reserve, out_amount)?;
+                (new_quote_reserve, new_base_reserve, out_amount)
+            },
         };
 
         self.base_reserve = new_base_reserve;
         self.quote_reserve = new_quote_reserve;
+        self.ensure_invariant()?;
 
         // Enforce slippage protection
         if let Some(min_amount_out) = min_amount_out {
@@ -38,7 +271,7 @@ impl Curve {
             }
         }
 
-        Ok(out_amount)
+        Ok((out_amount, Uint128::zero()))
     }
 
     pub fn sell(
@@ -46,15 +279,26 @@ impl Curve {
         in_amount: Uint128,
         min_amount_out: Option<Uint128>,
     ) -> Result<Uint128, ContractError> {
-        let (new_quote_reserve, new_base_reserve, out_amount) = {
-            let new_base_reserve = add_u128(self.base_reserve, in_amount)?;
-            let new_quote_reserve = div_u256(self.k, new_base_reserve)?.try_into().unwrap();
-            let out_amount = sub_u128(self.quote_reserve, new_quote_reserve)?;
-            (new_quote_reserve, new_base_reserve, out_amount)
+        let (new_quote_reserve, new_base_reserve, out_amount) = match self.kind {
+            CurveKind::ConstantProduct {} => {
+                let new_base_reserve = add_u256(self.base_reserve, in_amount)?;
+                let new_quote_reserve = div_ceil_u256(self.k, new_base_reserve)?;
+                let out_amount = to_u128(sub_u256(self.quote_reserve, new_quote_reserve)?)?;
+                (new_quote_reserve, new_base_reserve, out_amount)
+            },
+            _ => {
+                let new_base_reserve = add_u256(self.base_reserve, in_amount)?;
+                let sold = self.calculate_base_sold()?;
+                let new_sold = sub_u256(sold, in_amount)?;
+                let out_amount = to_u128(sub_u256(self.integrate(sold)?, self.integrate(new_sold)?)?)?;
+                let new_quote_reserve = sub_u256(self.quote_reserve, out_amount)?;
+                (new_quote_reserve, new_base_reserve, out_amount)
+            },
         };
 
         self.base_reserve = new_base_reserve;
         self.quote_reserve = new_quote_reserve;
+        self.ensure_invariant()?;
 
         // Enforce slippage protection
         if let Some(min_amount_out) = min_amount_out {
@@ -66,43 +310,741 @@ impl Curve {
         Ok(out_amount)
     }
 
-    /// Calculates BASE price with respect to QUOTE
-    pub fn calculate_quote_price(&self) -> Result<Uint128, ContractError> {
-        mul_ratio_u128(
-            self.quote_reserve,
-            10u128.pow(self.quote_decimals as u32),
-            self.base_reserve,
-        )
+    /// Buys exactly `out_amount` base, returning the quote amount it costs.
+    /// Rounds the cost up, in favor of the pool.
+    pub fn buy_exact_out(
+        &mut self,
+        out_amount: Uint128,
+        max_in_amount: Option<Uint128>,
+    ) -> Result<Uint128, ContractError> {
+        if Uint256::from(out_amount) > self.calculate_base_sellable()? {
+            return Err(ContractError::InsufficientLiquidity {});
+        }
+
+        let (new_quote_reserve, new_base_reserve, in_amount) = match self.kind {
+            CurveKind::ConstantProduct {} => {
+                if Uint256::from(out_amount) >= self.base_reserve {
+                    return Err(ContractError::InsufficientLiquidity {});
+                }
+                let new_base_reserve = sub_u256(self.base_reserve, out_amount)?;
+                let new_quote_reserve = div_ceil_u256(self.k, new_base_reserve)?.max(self.quote_reserve);
+                let in_amount = to_u128(sub_u256(new_quote_reserve, self.quote_reserve)?)?;
+                (new_quote_reserve, new_base_reserve, in_amount)
+            },
+            _ => {
+                let sold = self.calculate_base_sold()?;
+                let new_sold = add_u256(sold, out_amount)?;
+                let in_amount = to_u128(sub_u256(self.integrate(new_sold)?, self.integrate(sold)?)?)?;
+                let new_quote_reserve = add_u256(self.quote_reserve, in_amount)?;
+                let new_base_reserve = sub_u256(self.base_reserve, out_amount)?;
+                (new_quote_reserve, new_base_reserve, in_amount)
+            },
+        };
+
+        self.base_reserve = new_base_reserve;
+        self.quote_reserve = new_quote_reserve;
+        self.ensure_invariant()?;
+
+        // Enforce slippage protection
+        if let Some(max_in_amount) = max_in_amount {
+            if in_amount > max_in_amount {
+                return Err(ContractError::TooMuchSlippage {});
+            }
+        }
+
+        Ok(in_amount)
     }
 
+    /// Sells base for exactly `out_amount` quote, returning the base amount it
+    /// costs. Rounds the cost up, in favor of the pool.
+    pub fn sell_exact_out(
+        &mut self,
+        out_amount: Uint128,
+        max_in_amount: Option<Uint128>,
+    ) -> Result<Uint128, ContractError> {
+        let (new_quote_reserve, new_base_reserve, in_amount) = match self.kind {
+            CurveKind::ConstantProduct {} => {
+                if Uint256::from(out_amount) >= self.quote_reserve {
+                    return Err(ContractError::InsufficientLiquidity {});
+                }
+                let new_quote_reserve = sub_u256(self.quote_reserve, out_amount)?;
+                let new_base_reserve = div_ceil_u256(self.k, new_quote_reserve)?.max(self.base_reserve);
+                let in_amount = to_u128(sub_u256(new_base_reserve, self.base_reserve)?)?;
+                (new_quote_reserve, new_base_reserve, in_amount)
+            },
+            _ => {
+                let sold = self.calculate_base_sold()?;
+                let target =
+                    sub_u256(self.integrate(sold)?, out_amount).map_err(|_| ContractError::InsufficientLiquidity {})?;
+                let in_amount = to_u128(sub_u256(sold, self.solve_base_sold(Uint256::zero(), sold, target)?)?)?;
+                let new_quote_reserve = sub_u256(self.quote_reserve, out_amount)?;
+                let new_base_reserve = add_u256(self.base_reserve, in_amount)?;
+                (new_quote_reserve, new_base_reserve, in_amount)
+            },
+        };
+
+        self.base_reserve = new_base_reserve;
+        self.quote_reserve = new_quote_reserve;
+        self.ensure_invariant()?;
+
+        // Enforce slippage protection
+        if let Some(max_in_amount) = max_in_amount {
+            if in_amount > max_in_amount {
+                return Err(ContractError::TooMuchSlippage {});
+            }
+        }
+
+        Ok(in_amount)
+    }
+
+    /// Calculates BASE price with respect to QUOTE, i.e. whole quote tokens
+    /// per whole base token.
+    pub fn calculate_quote_price(&self) -> Result<Decimal256, ContractError> {
+        match self.kind {
+            CurveKind::ConstantProduct {} => from_ratio_dec256(
+                mul_u256(self.quote_reserve, decimal_scale(self.base_decimals)?)?,
+                mul_u256(self.base_reserve, decimal_scale(self.quote_decimals)?)?,
+            ),
+            _ => self.calculate_spot_price(),
+        }
+    }
+
+    /// Converts a quote amount into the base amount it's worth at the current
+    /// price, both in raw units.
     pub fn to_base_amount(
         &self,
         quote_amount: Uint128,
     ) -> Result<Uint128, ContractError> {
-        mul_ratio_u128(
-            quote_amount,
-            10u128.pow(self.quote_decimals as u32),
-            self.calculate_base_price()?,
-        )
+        to_u128(mul_ratio_u256(
+            mul_u256(quote_amount, self.calculate_base_price()?.atomics())?,
+            decimal_scale(self.base_decimals)?,
+            mul_u256(decimal_scale(self.quote_decimals)?, Decimal256::one().atomics())?,
+        )?)
     }
 
+    /// Converts a base amount into the quote amount it's worth at the current
+    /// price, both in raw units.
     pub fn to_quote_amount(
         &self,
         base_amount: Uint128,
     ) -> Result<Uint128, ContractError> {
-        mul_ratio_u128(
-            base_amount,
-            10u128.pow(self.base_decimals as u32),
+        to_u128(quote_value(
+            base_amount.into(),
             self.calculate_quote_price()?,
+            self.base_decimals,
+            self.quote_decimals,
+        )?)
+    }
+
+    /// Adds quote to the reserve without selling any base. For the constant
+    /// product curve, k grows to match, deepening the curve. For the others,
+    /// the quote is kept as surplus over what the curve owes sellers.
+    pub fn deposit_quote(
+        &mut self,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        self.quote_reserve = add_u256(self.quote_reserve, amount)?;
+        if let CurveKind::ConstantProduct {} = self.kind {
+            self.k = mul_u256(self.base_reserve, self.quote_reserve)?;
+        }
+        self.ensure_invariant()
+    }
+
+    /// Quote value of the curve's initial base supply at its start price, plus
+    /// the virtual quote reserve, in raw quote units.
+    pub fn calculate_virtual_liquidity(&self) -> Result<Uint256, ContractError> {
+        add_u256(
+            self.quote_reserve_virtual,
+            quote_value(
+                self.base_supply,
+                self.calculate_price_at(Uint256::zero())?,
+                self.base_decimals,
+                self.quote_decimals,
+            )?,
         )
     }
 
-    /// Calculates QUOTE price with respect to BASE
-    pub fn calculate_base_price(&self) -> Result<Uint128, ContractError> {
-        mul_ratio_u128(
-            self.base_reserve,
-            10u128.pow(self.quote_decimals as u32),
-            self.quote_reserve,
+    /// Marginal price, in whole quote tokens per whole base token, once `sold`
+    /// base tokens have been bought out of the curve.
+    pub fn calculate_price_at(
+        &self,
+        sold: Uint256,
+    ) -> Result<Decimal256, ContractError> {
+        let mut curve = self.clone();
+        curve.base_reserve = sub_u256(self.base_supply, sold)?;
+        if let CurveKind::ConstantProduct {} = self.kind {
+            curve.quote_reserve = div_u256(self.k, curve.base_reserve)?;
+        }
+        curve.calculate_quote_price()
+    }
+
+    /// Calculates QUOTE price with respect to BASE, i.e. whole base tokens per
+    /// whole quote token.
+    pub fn calculate_base_price(&self) -> Result<Decimal256, ContractError> {
+        match self.kind {
+            CurveKind::ConstantProduct {} => from_ratio_dec256(
+                mul_u256(self.base_reserve, decimal_scale(self.quote_decimals)?)?,
+                mul_u256(self.quote_reserve, decimal_scale(self.base_decimals)?)?,
+            ),
+            _ => div_dec256(Decimal256::one(), self.calculate_spot_price()?),
+        }
+    }
+
+    /// Ensures that the reserves still back the curve. For the constant product
+    /// curve, this means that k hasn't shrunk. For the others, it means that
+    /// the quote reserve covers the cost of all base sold so far.
+    pub fn ensure_invariant(&self) -> Result<(), ContractError> {
+        self.ensure_solvent()?;
+        let is_ok = match self.kind {
+            CurveKind::ConstantProduct {} => mul_u256(self.base_reserve, self.quote_reserve)? >= self.k,
+            _ => self.quote_reserve >= self.integrate(self.calculate_base_sold()?)?,
+        };
+        if !is_ok {
+            return Err(ContractError::InvariantViolation {});
+        }
+        Ok(())
+    }
+
+    /// Ensures that neither reserve has been drawn down into its virtual
+    /// portion, i.e. that every token paid out is actually held by the contract.
+    pub fn ensure_solvent(&self) -> Result<(), ContractError> {
+        if self.base_reserve < self.base_reserve_virtual || self.quote_reserve < self.quote_reserve_virtual {
+            return Err(ContractError::InsufficientLiquidity {});
+        }
+        Ok(())
+    }
+
+    /// Real base reserve held by the contract
+    pub fn calculate_base_reserve_real(&self) -> Result<Uint256, ContractError> {
+        sub_u256(self.base_reserve, self.base_reserve_virtual)
+    }
+
+    /// Real quote reserve held by the contract
+    pub fn calculate_quote_reserve_real(&self) -> Result<Uint256, ContractError> {
+        sub_u256(self.quote_reserve, self.quote_reserve_virtual)
+    }
+
+    /// Amount of base supply bought out of the curve so far
+    pub fn calculate_base_sold(&self) -> Result<Uint256, ContractError> {
+        sub_u256(self.base_supply, self.base_reserve)
+    }
+
+    /// Amount of base left to sell before reaching the sellable supply
+    pub fn calculate_base_sellable(&self) -> Result<Uint256, ContractError> {
+        Ok(self.sellable_supply.saturating_sub(self.calculate_base_sold()?))
+    }
+
+    /// Marginal price of the next base token, in whole quote tokens per whole
+    /// base token, for the supply-driven curve shapes.
+    fn calculate_spot_price(&self) -> Result<Decimal256, ContractError> {
+        let x = from_ratio_dec256(self.calculate_base_sold()?, self.base_supply)?;
+        match self.kind {
+            CurveKind::ConstantProduct {} => Err(ContractError::ValidationError {
+                reason: "constant product curve has no supply-driven price".to_owned(),
+            }),
+            CurveKind::Linear { start_price, end_price } => {
+                add_dec256(start_price, mul_dec256(sub_dec256(end_price, start_price)?, x)?)
+            },
+            CurveKind::PowerLaw {
+                start_price,
+                end_price,
+                exponent,
+            } => add_dec256(
+                start_price,
+                mul_dec256(sub_dec256(end_price, start_price)?, pow_dec256(x, exponent as u32)?)?,
+            ),
+            CurveKind::Exponential { start_price, growth } => {
+                mul_dec256(start_price, exp_dec256(mul_dec256(growth, x)?)?)
+            },
+            CurveKind::Stepped { ref tranches } => {
+                let sold = self.calculate_base_sold()?;
+                let ranges = self.calculate_tranche_ranges(tranches)?;
+                let (_, _, price) = ranges
+                    .iter()
+                    .find(|(_, end, _)| sold < *end)
+                    .or(ranges.last())
+                    .ok_or(ContractError::InsufficientLiquidity {})?;
+                Ok(*price)
+            },
+        }
+    }
+
+    /// Splits the base supply into the raw amount range sold by each tranche,
+    /// paired with the tranche's price.
+    pub fn calculate_tranche_ranges(
+        &self,
+        tranches: &[Tranche],
+    ) -> Result<Vec<(Uint256, Uint256, Decimal256)>, ContractError> {
+        let mut ranges = Vec::with_capacity(tranches.len());
+        let mut start = Uint256::zero();
+        let mut total_pct = Uint128::zero();
+        for tranche in tranches.iter() {
+            total_pct = add_u128(total_pct, tranche.supply_pct)?;
+            let end = mul_ratio_u256(self.base_supply, total_pct, 1_000_000u128)?;
+            ranges.push((start, end, tranche.price));
+            start = end;
+        }
+        Ok(ranges)
+    }
+
+    /// Total quote amount it takes to buy `sold` base tokens out of the curve,
+    /// starting from zero. Rounded down, so it's monotonic in `sold`, making
+    /// any sequence of swaps path-independent.
+    fn integrate(
+        &self,
+        sold: Uint256,
+    ) -> Result<Uint256, ContractError> {
+        let x = from_ratio_dec256(sold, self.base_supply)?;
+        let supply = from_ratio_dec256(self.base_supply, Uint256::one())?;
+        let sold = from_ratio_dec256(sold, Uint256::one())?;
+        let area = match self.kind {
+            CurveKind::ConstantProduct {} => {
+                return Err(ContractError::ValidationError {
+                    reason: "constant product curve has no supply-driven integral".to_owned(),
+                })
+            },
+            CurveKind::Linear { start_price, end_price } => {
+                Self::integrate_power_law(start_price, end_price, 1, x, sold, supply)?
+            },
+            CurveKind::PowerLaw {
+                start_price,
+                end_price,
+                exponent,
+            } => Self::integrate_power_law(start_price, end_price, exponent, x, sold, supply)?,
+            CurveKind::Stepped { ref tranches } => {
+                let mut area = Decimal256::zero();
+                for (start, end, price) in self.calculate_tranche_ranges(tranches)? {
+                    let start = from_ratio_dec256(start, Uint256::one())?;
+                    if sold <= start {
+                        break;
+                    }
+                    let amount = sub_dec256(sold.min(from_ratio_dec256(end, Uint256::one())?), start)?;
+                    area = add_dec256(area, mul_dec256(price, amount)?)?;
+                }
+                area
+            },
+            CurveKind::Exponential { start_price, growth } => {
+                let growth_factor = sub_dec256(exp_dec256(mul_dec256(growth, x)?)?, Decimal256::one())?;
+                div_dec256(mul_dec256(mul_dec256(start_price, supply)?, growth_factor)?, growth)?
+            },
+        };
+        // The area is in whole quote tokens per whole base token, times base
+        // raw units. Rescale it into quote raw units.
+        mul_ratio_u256(
+            area.atomics(),
+            decimal_scale(self.quote_decimals)?,
+            mul_u256(decimal_scale(self.base_decimals)?, Decimal256::one().atomics())?,
         )
     }
+
+    fn integrate_power_law(
+        start_price: Decimal256,
+        end_price: Decimal256,
+        exponent: u8,
+        x: Decimal256,
+        sold: Decimal256,
+        supply: Decimal256,
+    ) -> Result<Decimal256, ContractError> {
+        let n = exponent as u32 + 1;
+        let flat = mul_dec256(start_price, sold)?;
+        let rise = div_dec256(
+            mul_dec256(
+                mul_dec256(sub_dec256(end_price, start_price)?, supply)?,
+                pow_dec256(x, n)?,
+            )?,
+            Decimal256::from_ratio(n, 1u128),
+        )?;
+        add_dec256(flat, rise)
+    }
+
+    /// Binary searches for the largest amount sold, between `lo` and `hi`,
+    /// whose integral doesn't exceed `target`.
+    fn solve_base_sold(
+        &self,
+        lo: Uint256,
+        hi: Uint256,
+        target: Uint256,
+    ) -> Result<Uint256, ContractError> {
+        let mut lo = lo;
+        let mut hi = hi;
+        if self.integrate(hi)? <= target {
+            return Ok(hi);
+        }
+        while sub_u256(hi, lo)? > Uint256::one() {
+            let mid = add_u256(lo, div_u256(sub_u256(hi, lo)?, 2u128)?)?;
+            if self.integrate(mid)? <= target {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        Ok(lo)
+    }
+}
+
+/// Raw quote value of a raw base amount at the given price
+fn quote_value(
+    base_amount: Uint256,
+    price: Decimal256,
+    base_decimals: u8,
+    quote_decimals: u8,
+) -> Result<Uint256, ContractError> {
+    mul_ratio_u256(
+        mul_u256(base_amount, price.atomics())?,
+        decimal_scale(quote_decimals)?,
+        mul_u256(decimal_scale(base_decimals)?, Decimal256::one().atomics())?,
+    )
+}
+
+/// One whole token in raw units
+fn decimal_scale(decimals: u8) -> Result<Uint256, ContractError> {
+    Uint256::from(10u128)
+        .checked_pow(decimals as u32)
+        .map_err(|e| ContractError::Std(StdError::overflow(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE_18: u128 = 1_000_000_000_000_000_000;
+
+    fn constant_product(
+        base_reserve: u128,
+        base_decimals: u8,
+        quote_reserve: u128,
+        quote_decimals: u8,
+    ) -> Curve {
+        Curve {
+            kind: CurveKind::ConstantProduct {},
+            k: Uint256::from(base_reserve) * Uint256::from(quote_reserve),
+            base_supply: base_reserve.into(),
+            sellable_supply: base_reserve.into(),
+            base_reserve: base_reserve.into(),
+            base_reserve_virtual: Uint256::zero(),
+            base_decimals,
+            quote_reserve: quote_reserve.into(),
+            quote_reserve_virtual: quote_reserve.into(),
+            quote_decimals,
+        }
+    }
+
+    #[test]
+    fn prices_18_decimal_quote_against_6_decimal_base() {
+        // 1B base tokens against 30k virtual quote tokens
+        let curve = constant_product(1_000_000_000_000_000, 6, 30_000 * ONE_18, 18);
+        assert_eq!(
+            curve.calculate_quote_price().unwrap(),
+            Decimal256::from_ratio(3u128, 100_000u128)
+        );
+        assert_eq!(
+            curve.calculate_base_price().unwrap(),
+            Decimal256::from_ratio(100_000u128, 3u128)
+        );
+        assert_eq!(
+            curve.to_quote_amount(Uint128::new(1_000_000)).unwrap(),
+            Uint128::new(30_000_000_000_000)
+        );
+    }
+
+    #[test]
+    fn swaps_18_decimal_reserves_beyond_u128_product() {
+        // 1B base and 1B quote tokens, both with 18 decimals, so k ~ 1e54
+        let mut curve = constant_product(1_000_000_000 * ONE_18, 18, 1_000_000_000 * ONE_18, 18);
+        let in_amount = Uint128::new(1_000_000 * ONE_18);
+
+        let (out_amount, unused_in_amount) = curve.buy(in_amount, None).unwrap();
+        assert!(unused_in_amount.is_zero());
+        assert!(out_amount > Uint128::new(999_000 * ONE_18) && out_amount < Uint128::new(1_000_000 * ONE_18));
+        assert!(curve.calculate_quote_price().unwrap() > Decimal256::one());
+
+        let quote_out = curve.sell(out_amount, None).unwrap();
+        assert!(quote_out <= in_amount);
+        assert!(in_amount - quote_out <= Uint128::new(1));
+    }
+
+    #[test]
+    fn exact_out_with_18_decimal_quote() {
+        let mut curve = constant_product(800_000_000_000_000, 6, 30_000 * ONE_18, 18);
+        let out_amount = Uint128::new(10_000_000_000_000);
+        let in_amount = curve.clone().buy_exact_out(out_amount, None).unwrap();
+        let (bought, _) = curve.buy(in_amount, None).unwrap();
+        assert!(bought >= out_amount);
+    }
+
+    #[test]
+    fn linear_curve_with_18_decimal_tokens() {
+        let mut curve = Curve {
+            kind: CurveKind::Linear {
+                start_price: Decimal256::from_ratio(1u128, 1_000_000u128),
+                end_price: Decimal256::from_ratio(1u128, 10_000u128),
+            },
+            ..constant_product(1_000_000_000 * ONE_18, 18, 0, 18)
+        };
+        let (out_amount, _) = curve.buy(Uint128::new(1_000 * ONE_18), None).unwrap();
+
+        // Solving the integral of the price for 1k quote gives ~132,391,272.52
+        assert!(out_amount > Uint128::new(132_391_272 * ONE_18));
+        assert!(out_amount < Uint128::new(132_391_273 * ONE_18));
+        assert!(curve.calculate_quote_price().unwrap() > Decimal256::from_ratio(1u128, 1_000_000u128));
+    }
+
+    #[test]
+    fn swaps_never_shrink_constant_product() {
+        let mut curve = constant_product(800_000_000_000_000, 6, 30_000 * ONE_18, 18);
+        for i in 1..50u128 {
+            let (out_amount, _) = curve.buy(Uint128::new(i * 7_777_777_777_777_777), None).unwrap();
+            curve.sell(out_amount.multiply_ratio(2u128, 3u128), None).unwrap();
+            curve.buy_exact_out(Uint128::new(i * 333_333), None).unwrap();
+            curve.sell_exact_out(Uint128::new(i * 11_111_111_111), None).unwrap();
+            assert!(Uint256::from(curve.base_reserve) * curve.quote_reserve >= curve.k);
+        }
+    }
+
+    #[test]
+    fn rejects_swaps_that_break_the_invariant() {
+        let mut curve = constant_product(1_000_000_000_000, 6, 0, 6);
+        curve.kind = CurveKind::Linear {
+            start_price: Decimal256::percent(1),
+            end_price: Decimal256::one(),
+        };
+        curve.k = Uint256::zero();
+        curve.buy(Uint128::new(1_000_000_000), None).unwrap();
+
+        // Reserve drifts one unit below what the curve owes sellers
+        curve.quote_reserve -= Uint256::one();
+        assert!(matches!(
+            curve.sell(Uint128::new(1_000_000), None),
+            Err(ContractError::InvariantViolation {})
+        ));
+    }
+
+    #[test]
+    fn derives_reserves_from_targets() {
+        // 1B base tokens, starting at 0.00003 and graduating at a 400k market
+        // cap once 80% of the supply is sold
+        let targets = CurveTargets {
+            start_price: Decimal256::from_ratio(3u128, 100_000u128),
+            graduation_market_cap: Uint256::from(400_000 * ONE_18),
+            graduation_supply_pct: Uint128::new(800_000),
+        };
+        let supply = Uint128::new(1_000_000_000_000_000);
+        let (base_reserve, quote_reserve, sellable_supply) = targets.derive_reserves(supply, 6, 18).unwrap();
+        assert_eq!(sellable_supply, Uint256::from(800_000_000_000_000u128));
+
+        let mut curve = constant_product(1, 6, 1, 18);
+        curve.k = base_reserve * quote_reserve;
+        curve.base_supply = base_reserve;
+        curve.base_reserve = base_reserve;
+        curve.quote_reserve = quote_reserve;
+        curve.sellable_supply = sellable_supply;
+
+        let tolerance = Decimal256::from_ratio(1u128, 1_000_000u128);
+        let start_price = curve.calculate_price_at(Uint256::zero()).unwrap();
+        assert!(start_price.abs_diff(targets.start_price) < tolerance * targets.start_price);
+
+        let graduation_price = Decimal256::from_ratio(400_000u128, 1_000_000_000u128);
+        let end_price = curve.calculate_price_at(sellable_supply).unwrap();
+        assert!(end_price.abs_diff(graduation_price) < tolerance * graduation_price);
+
+        // Over a larger supply, the same market cap implies a lower price
+        assert!(targets.derive_reserves(Uint128::new(ONE_18), 6, 18).is_err());
+
+        // Graduating at a 100x price after selling half the supply calls for a
+        // curve reserve of only 555M, which would leave 445M minted tokens off
+        // the curve
+        let targets = CurveTargets {
+            graduation_market_cap: Uint256::from(3_000_000 * ONE_18),
+            graduation_supply_pct: Uint128::new(500_000),
+            ..targets
+        };
+        assert!(matches!(
+            targets.derive_reserves(supply, 6, 18),
+            Err(ContractError::ValidationError { reason }) if reason.starts_with("graduation market cap is too high")
+        ));
+
+        // Whereas selling 90% by then needs a reserve of exactly the supply
+        let targets = CurveTargets {
+            graduation_supply_pct: Uint128::new(900_000),
+            ..targets
+        };
+        let (base_reserve, _, _) = targets.derive_reserves(supply, 6, 18).unwrap();
+        assert_eq!(base_reserve, Uint256::from(1_000_000_000_000_000u128));
+    }
+
+    #[test]
+    fn virtual_base_reserve_is_never_sold() {
+        // 600M real base tokens priced as if there were 1B
+        let mut curve = constant_product(1_000_000_000_000_000, 6, 30_000 * ONE_18, 18);
+        curve.base_reserve_virtual = Uint256::from(400_000_000_000_000u128);
+        curve.sellable_supply = curve.calculate_base_reserve_real().unwrap();
+
+        let (out_amount, unused_in_amount) = curve.buy(Uint128::new(1_000_000 * ONE_18), None).unwrap();
+        assert_eq!(out_amount, Uint128::new(600_000_000_000_000));
+        assert!(!unused_in_amount.is_zero());
+        assert!(curve.calculate_base_reserve_real().unwrap().is_zero());
+
+        // Without the sellable supply as a backstop, the solvency check kicks in
+        let mut curve = constant_product(1_000_000_000_000_000, 6, 30_000 * ONE_18, 18);
+        curve.base_reserve_virtual = Uint256::from(400_000_000_000_000u128);
+        assert!(matches!(
+            curve.buy(Uint128::new(1_000_000 * ONE_18), None),
+            Err(ContractError::InsufficientLiquidity {})
+        ));
+    }
+
+    #[test]
+    fn stepped_curve_prices_tranches_piecewise() {
+        let mut curve = Curve {
+            kind: CurveKind::Stepped {
+                tranches: vec![
+                    Tranche {
+                        supply_pct: Uint128::new(100_000),
+                        price: Decimal256::permille(1),
+                    },
+                    Tranche {
+                        supply_pct: Uint128::new(100_000),
+                        price: Decimal256::permille(2),
+                    },
+                    Tranche {
+                        supply_pct: Uint128::new(800_000),
+                        price: Decimal256::percent(1),
+                    },
+                ],
+            },
+            ..constant_product(1_000_000_000_000, 6, 0, 6)
+        };
+        curve.kind.validate().unwrap();
+
+        // 100 quote buys out the first tranche and the other 50 buy 25k tokens
+        // from the second one, give or take a raw quote unit's worth
+        let (out_amount, _) = curve.buy(Uint128::new(150_000_000), None).unwrap();
+        assert!(out_amount >= Uint128::new(125_000_000_000) && out_amount < Uint128::new(125_000_000_500));
+        assert_eq!(curve.calculate_quote_price().unwrap(), Decimal256::permille(2));
+
+        let quote_out = curve.sell(out_amount, None).unwrap();
+        assert_eq!(quote_out, Uint128::new(150_000_000));
+        assert_eq!(curve.calculate_quote_price().unwrap(), Decimal256::permille(1));
+    }
+
+    /// Curve of the given shape over 1B 6-decimal base tokens
+    fn supply_driven(kind: CurveKind) -> Curve {
+        kind.validate().unwrap();
+        Curve {
+            kind,
+            ..constant_product(1_000_000_000_000_000, 6, 0, 6)
+        }
+    }
+
+    /// Buys with `in_amount`, sells the output back and then checks that
+    /// exact-out swaps deliver what they promise
+    fn assert_round_trips(
+        mut curve: Curve,
+        in_amount: Uint128,
+    ) {
+        let (out_amount, _) = curve.clone().buy(in_amount, None).unwrap();
+        let mut bought = curve.clone();
+        bought.buy(in_amount, None).unwrap();
+        let quote_out = bought.sell(out_amount, None).unwrap();
+        assert!(quote_out <= in_amount);
+        assert!(in_amount - quote_out <= Uint128::new(1));
+
+        let exact_in = curve.clone().buy_exact_out(out_amount, None).unwrap();
+        assert!(exact_in <= in_amount);
+        let (exact_out, _) = curve.buy(exact_in, None).unwrap();
+        assert!(exact_out >= out_amount);
+
+        let base_in = curve.clone().sell_exact_out(exact_in, None).unwrap();
+        assert!(curve.sell(base_in, None).unwrap() >= exact_in);
+    }
+
+    #[test]
+    fn exponential_curve_prices_along_growth() {
+        let kind = CurveKind::Exponential {
+            start_price: Decimal256::from_ratio(1u128, 1_000_000u128),
+            growth: Decimal256::from_ratio(2u128, 1u128),
+        };
+        let mut curve = supply_driven(kind.clone());
+
+        // Spending the supply's start value of 1k quote solves e^(2x) = 3, so
+        // x = ln(3) / 2 ~ 0.5493061443 of the supply is sold
+        let (out_amount, _) = curve.buy(Uint128::new(1_000_000_000), None).unwrap();
+        assert!(out_amount > Uint128::new(549_306_144_000_000) && out_amount < Uint128::new(549_306_145_000_000));
+        let price = curve.calculate_quote_price().unwrap();
+        let tolerance = Decimal256::from_ratio(1u128, 1_000_000_000u128);
+        assert!(price.abs_diff(Decimal256::from_ratio(3u128, 1_000_000u128)) < tolerance);
+
+        assert_round_trips(supply_driven(kind), Uint128::new(1_000_000_000));
+    }
+
+    #[test]
+    fn power_law_curve_prices_along_exponent() {
+        let kind = CurveKind::PowerLaw {
+            start_price: Decimal256::from_ratio(1u128, 1_000_000u128),
+            end_price: Decimal256::from_ratio(1u128, 10_000u128),
+            exponent: 2,
+        };
+        let mut curve = supply_driven(kind.clone());
+
+        // Half the supply costs 1B * (0.000001 * 0.5 + 0.000099 * 0.5^3 / 3),
+        // i.e. 4,625 quote, after which the price is 0.00002575
+        let (out_amount, _) = curve.buy(Uint128::new(4_625_000_000), None).unwrap();
+        assert!(out_amount > Uint128::new(499_999_999_000_000) && out_amount < Uint128::new(500_000_001_000_000));
+        let price = curve.calculate_quote_price().unwrap();
+        let tolerance = Decimal256::from_ratio(1u128, 1_000_000_000u128);
+        assert!(price.abs_diff(Decimal256::from_ratio(2_575u128, 100_000_000u128)) < tolerance);
+
+        assert_round_trips(supply_driven(kind), Uint128::new(4_625_000_000));
+    }
+
+    #[test]
+    fn rejects_exponential_growth_out_of_range() {
+        let exponential = |growth: Decimal256| CurveKind::Exponential {
+            start_price: Decimal256::percent(1),
+            growth,
+        };
+        let max_growth = Decimal256::from_ratio(MAX_EXPONENTIAL_GROWTH, 1u128);
+        exponential(max_growth).validate().unwrap();
+        for growth in [
+            Decimal256::zero(),
+            max_growth + Decimal256::from_ratio(1u128, 1_000_000u128),
+        ] {
+            assert!(matches!(
+                exponential(growth).validate(),
+                Err(ContractError::ValidationError { reason }) if reason == "exponential curve growth out of range"
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_power_law_exponent_out_of_range() {
+        let power_law = |exponent: u8| CurveKind::PowerLaw {
+            start_price: Decimal256::percent(1),
+            end_price: Decimal256::one(),
+            exponent,
+        };
+        power_law(1).validate().unwrap();
+        power_law(MAX_POWER_LAW_EXPONENT).validate().unwrap();
+        for exponent in [0, MAX_POWER_LAW_EXPONENT + 1] {
+            assert!(matches!(
+                power_law(exponent).validate(),
+                Err(ContractError::ValidationError { reason }) if reason == "power-law curve exponent out of range"
+            ));
+        }
+    }
+
+    #[test]
+    fn deposited_quote_grows_constant_product() {
+        let mut curve = constant_product(800_000_000_000_000, 6, 30_000 * ONE_18, 18);
+        let k_initial = curve.k;
+        let (out_amount, _) = curve.buy(Uint128::new(1_000 * ONE_18), None).unwrap();
+        curve.deposit_quote(Uint128::new(10 * ONE_18)).unwrap();
+        assert!(curve.k > k_initial);
+
+        // Selling everything back pays out part of the deposit and leaves the
+        // rest in the real reserve
+        let quote_out = curve.sell(out_amount, None).unwrap();
+        assert!(quote_out > Uint128::new(1_000 * ONE_18));
+        assert!(curve.calculate_quote_reserve_real().unwrap() > Uint256::zero());
+    }
 }
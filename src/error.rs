@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{ConversionOverflowError, StdError};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -6,6 +6,9 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    ConversionOverflow(#[from] ConversionOverflowError),
+
     #[error("InsufficientFunds: Expected {exp_amount} {denom} but got {amount}")]
     InsufficientFunds {
         denom: String,
@@ -24,6 +27,15 @@ pub enum ContractError {
 
     #[error("TooMuchSlippage: Exceeded slippage tolerance")]
     TooMuchSlippage {},
+
+    #[error("InsufficientLiquidity: Not enough base reserve to fill swap")]
+    InsufficientLiquidity {},
+
+    #[error("InvariantViolation: Swap would leave the curve undercollateralized")]
+    InvariantViolation {},
+
+    #[error("PriceImpactTooHigh: Price impact of {impact_bps} bps exceeds max of {max_impact_bps} bps")]
+    PriceImpactTooHigh { impact_bps: u128, max_impact_bps: u32 },
 }
 
 impl From<ContractError> for StdError {
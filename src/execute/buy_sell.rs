@@ -1,16 +1,23 @@
-use cosmwasm_std::{attr, Response, Storage, SubMsg, Uint128};
+use cosmwasm_std::{attr, Addr, Api, Decimal256, Env, MessageInfo, Response, Storage, SubMsg, Uint128};
 
 use crate::{
+    curve::Curve,
     error::ContractError,
-    math::{add_u128, add_u32, add_u64, mul_pct_u128, sub_u128},
-    models::{account::MaxSwapInfo, ohlc::OhlcBar},
-    msg::{BuyMsg, SellMsg},
+    math::{add_u128, add_u256, add_u32, add_u64, mul_dec256, mul_pct_u128, mul_ratio_u128, sub_u128, to_u128},
+    models::{
+        account::{record_daily_volume, MaxSwapInfo},
+        fee::FeeAsset,
+        ohlc::OhlcBar,
+    },
+    msg::{BuyExactOutMsg, BuyMsg, SellExactOutMsg, SellMsg},
+    quote::{quote_buy, quote_buy_exact_out, quote_sell, quote_sell_exact_out, SwapQuote},
     state::{
-        ACCOUNT_STATS, BASE_TOKEN, CURVE, FEE_ADDR, FEE_PCT_BUY, FEE_PCT_SELL, MAKER_STATS, NET_MAKER_FEE,
-        NET_TAKER_FEE, QUOTE_TOKEN, TAKER_STATS,
+        ACCOUNT_STATS, BASE_TOKEN, CLAIMABLE_FEES, CURVE, FEE_ADDR, FEE_ASSET_BUY, FEE_ASSET_SELL, FEE_RECIPIENTS,
+        FEE_RECIPIENT_BASE_TOTALS, FEE_RECIPIENT_TOTALS, LP_FEE_PCT, LP_FEE_RETAINED, MAKER_STATS, NET_MAKER_FEE,
+        NET_TAKER_FEE, QUOTE_TOKEN, REFEREES, REFERRAL_FEE_PCT, REFERRER_STATS, TAKER_STATS,
     },
     token::Token,
-    utils::resolve_swap_initiator,
+    utils::{resolve_buy_fee_pct, resolve_sell_fee_pct, resolve_swap_initiator},
 };
 
 use super::Context;
@@ -23,56 +30,241 @@ pub fn exec_buy(
     let Context { deps, info, env } = ctx;
     let BuyMsg {
         initiator,
+        referrer,
         min_out_amount,
+        max_price_impact_bps,
     } = msg;
 
+    // Get initiator. The initiator is either the user performing the tx or the
+    // user on whose behalf the operator is performing it.
+    let initiator = resolve_swap_initiator(deps.storage, deps.api, &info.sender, amount.is_some(), initiator, "buy")?;
+    let referrer = resolve_referrer(deps.api, &initiator, referrer)?;
+
     let mut curve = CURVE.load(deps.storage)?;
     let quote_token = QUOTE_TOKEN.load(deps.storage)?;
+
+    // Amount we're trying to swap in
+    let in_amount = resolve_quote_in_amount(&quote_token, &info, amount)?;
+
+    // Perform AMM swap, net of the buy-side platform fee
+    let quote = quote_buy(
+        &mut curve,
+        resolve_buy_fee_pct(deps.as_ref(), env.block.time, Some(&initiator))?,
+        LP_FEE_PCT.load(deps.storage)?,
+        FEE_ASSET_BUY.load(deps.storage)?,
+        in_amount,
+        min_out_amount,
+    )?;
+    ensure_price_impact(&quote, max_price_impact_bps)?;
+
+    settle_buy(deps.storage, &env, &initiator, referrer.as_ref(), &curve, "buy", quote)
+}
+
+pub fn exec_buy_exact_out(
+    ctx: Context,
+    msg: BuyExactOutMsg,
+    amount: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    let Context { deps, info, env } = ctx;
+    let BuyExactOutMsg {
+        initiator,
+        referrer,
+        out_amount,
+        max_in_amount,
+        max_price_impact_bps,
+    } = msg;
+
+    let initiator = resolve_swap_initiator(
+        deps.storage,
+        deps.api,
+        &info.sender,
+        amount.is_some(),
+        initiator,
+        "buy_exact_out",
+    )?;
+    let referrer = resolve_referrer(deps.api, &initiator, referrer)?;
+
+    let mut curve = CURVE.load(deps.storage)?;
+    let quote_token = QUOTE_TOKEN.load(deps.storage)?;
+
+    // Total amount sent, which caps what the buy can cost
+    let sent_amount = resolve_quote_in_amount(&quote_token, &info, amount)?;
+
+    // Perform AMM swap, grossed up by the buy-side platform fee
+    let mut quote = quote_buy_exact_out(
+        &mut curve,
+        resolve_buy_fee_pct(deps.as_ref(), env.block.time, Some(&initiator))?,
+        LP_FEE_PCT.load(deps.storage)?,
+        FEE_ASSET_BUY.load(deps.storage)?,
+        out_amount,
+        max_in_amount,
+    )?;
+    ensure_price_impact(&quote, max_price_impact_bps)?;
+
+    if quote.in_amount > sent_amount {
+        return Err(ContractError::InsufficientFunds {
+            denom: quote_token.to_key(),
+            amount: sent_amount.u128(),
+            exp_amount: quote.in_amount.u128(),
+        });
+    }
+
+    quote.refund_amount = sub_u128(sent_amount, quote.in_amount)?;
+
+    settle_buy(
+        deps.storage,
+        &env,
+        &initiator,
+        referrer.as_ref(),
+        &curve,
+        "buy_exact_out",
+        quote,
+    )
+}
+
+pub fn exec_sell(
+    ctx: Context,
+    msg: SellMsg,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let Context { deps, info, env } = ctx;
+    let SellMsg {
+        initiator,
+        referrer,
+        min_out_amount,
+        max_price_impact_bps,
+    } = msg;
+
+    // Get initiator. The initiator is either the user performing the tx or user
+    // on whose behalf the operator is performing it.
+    let initiator = resolve_swap_initiator(deps.storage, deps.api, &info.sender, true, initiator, "sell")?;
+    let referrer = resolve_referrer(deps.api, &initiator, referrer)?;
+
+    let mut curve = CURVE.load(deps.storage)?;
+
+    // Perform AMM swap, net of the sell-side platform fee
+    let quote = quote_sell(
+        &mut curve,
+        resolve_sell_fee_pct(deps.as_ref(), env.block.time, Some(&initiator))?,
+        LP_FEE_PCT.load(deps.storage)?,
+        FEE_ASSET_SELL.load(deps.storage)?,
+        amount,
+        min_out_amount,
+    )?;
+    ensure_price_impact(&quote, max_price_impact_bps)?;
+
+    settle_sell(deps.storage, &env, &initiator, referrer.as_ref(), &curve, "sell", quote)
+}
+
+pub fn exec_sell_exact_out(
+    ctx: Context,
+    msg: SellExactOutMsg,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let Context { deps, info, env } = ctx;
+    let SellExactOutMsg {
+        initiator,
+        referrer,
+        out_amount,
+        max_in_amount,
+        max_price_impact_bps,
+    } = msg;
+
+    let initiator = resolve_swap_initiator(deps.storage, deps.api, &info.sender, true, initiator, "sell_exact_out")?;
+    let referrer = resolve_referrer(deps.api, &initiator, referrer)?;
+
+    let mut curve = CURVE.load(deps.storage)?;
     let base_token = BASE_TOKEN.load(deps.storage)?;
 
-    // Amount we're trying to swap in. If ammount is None, it implies that the
-    // quote token is a native coin in info.funds; otherwise, it's a CW20
-    let in_amount_pre_fee = if let Some(amount) = amount {
-        amount
-    } else {
-        if let Some(coin) = quote_token.find_in_funds(&info.funds, None) {
-            coin.amount
-        } else {
-            return Err(ContractError::MissingFunds {
-                denom: quote_token.get_denom().unwrap(),
-            });
-        }
-    };
+    // Perform AMM swap, grossed up by the sell-side platform fee
+    let mut quote = quote_sell_exact_out(
+        &mut curve,
+        resolve_sell_fee_pct(deps.as_ref(), env.block.time, Some(&initiator))?,
+        LP_FEE_PCT.load(deps.storage)?,
+        FEE_ASSET_SELL.load(deps.storage)?,
+        out_amount,
+        max_in_amount,
+    )?;
+    ensure_price_impact(&quote, max_price_impact_bps)?;
 
-    // Compute buy or sell-side platform fee
-    let fee_amount = mul_pct_u128(in_amount_pre_fee, FEE_PCT_BUY.load(deps.storage)?)?;
+    if quote.in_amount > amount {
+        return Err(ContractError::InsufficientFunds {
+            denom: base_token.to_key(),
+            amount: amount.u128(),
+            exp_amount: quote.in_amount.u128(),
+        });
+    }
 
-    // Increment total historical aggregate fee amount
-    NET_TAKER_FEE.update(deps.storage, |n| -> Result<_, ContractError> {
-        add_u128(n, fee_amount)
-    })?;
+    quote.refund_amount = sub_u128(amount, quote.in_amount)?;
+
+    settle_sell(
+        deps.storage,
+        &env,
+        &initiator,
+        referrer.as_ref(),
+        &curve,
+        "sell_exact_out",
+        quote,
+    )
+}
 
-    // Subtract fee from amount recieved by sender
-    let in_amount = sub_u128(in_amount_pre_fee, fee_amount)?;
+/// Persist the curve and stats after a buy and build the response
+fn settle_buy(
+    store: &mut dyn Storage,
+    env: &Env,
+    initiator: &Addr,
+    referrer: Option<&Addr>,
+    curve: &Curve,
+    action: &str,
+    quote: SwapQuote,
+) -> Result<Response, ContractError> {
+    let SwapQuote {
+        in_amount: in_amount_pre_fee,
+        out_amount,
+        fee_amount,
+        fee_pct,
+        fee_asset,
+        lp_fee_amount,
+        refund_amount,
+        ..
+    } = quote;
 
-    // Perform AMM swap
-    let out_amount = curve.buy(in_amount, min_out_amount)?;
+    let quote_token = QUOTE_TOKEN.load(store)?;
+    let base_token = BASE_TOKEN.load(store)?;
 
-    // Get initiator. The initiator is either the user performing the tx or the
-    // user on whose behalf the operator is performing it.
-    let initiator = resolve_swap_initiator(deps.storage, deps.api, &info.sender, amount.is_some(), initiator, "buy")?;
+    // Amount that actually went into the curve
+    let in_amount = match fee_asset {
+        FeeAsset::Quote => sub_u128(in_amount_pre_fee, fee_amount)?,
+        FeeAsset::Base => in_amount_pre_fee,
+    };
+
+    // The LP portion of the fee stays in the curve's reserves
+    let fee_amount = record_lp_fee(store, fee_amount, lp_fee_amount)?;
+
+    CURVE.save(store, curve)?;
+
+    // Increment total historical aggregate fee amount
+    NET_TAKER_FEE.update(store, |n| n.add(fee_asset, fee_amount))?;
+
+    // Credit the referrer with the swap and carve its share out of the fee
+    let referral_fee_amount = match referrer {
+        Some(referrer) => record_referral(store, referrer, initiator, in_amount_pre_fee, fee_amount, fee_asset)?,
+        None => Uint128::zero(),
+    };
+    let fee_amount = sub_u128(fee_amount, referral_fee_amount)?;
 
     // Update initiator's account info
-    ACCOUNT_STATS.update(deps.storage, &initiator, |maybe_stats| -> Result<_, ContractError> {
+    record_daily_volume(store, initiator, env.block.time, in_amount_pre_fee)?;
+    ACCOUNT_STATS.update(store, initiator, |maybe_stats| -> Result<_, ContractError> {
         let mut stats = maybe_stats.unwrap_or_default();
         stats.n_buys = add_u32(stats.n_buys, 1)?;
-        stats.net_quote_in = add_u128(stats.net_quote_in, in_amount)?;
-        stats.net_base_out = add_u128(stats.net_base_out, out_amount)?;
+        stats.net_quote_in = add_u256(stats.net_quote_in, in_amount)?;
+        stats.net_base_out = add_u256(stats.net_base_out, out_amount)?;
         Ok(stats)
     })?;
 
     // Update global stats
-    TAKER_STATS.update(deps.storage, |mut stats| -> Result<_, ContractError> {
+    TAKER_STATS.update(store, |mut stats| -> Result<_, ContractError> {
         stats.n = add_u64(stats.n, 1u64)?;
         if stats.max.is_none() || stats.max.clone().and_then(|m| Some(out_amount > m.amount)).unwrap() {
             stats.max = Some(MaxSwapInfo {
@@ -86,7 +278,7 @@ pub fn exec_buy(
 
     // Update candlestick data
     OhlcBar::upsert(
-        deps.storage,
+        store,
         env.block.time,
         curve.calculate_quote_price()?,
         out_amount,
@@ -94,66 +286,93 @@ pub fn exec_buy(
     )?;
 
     let mut resp = Response::new().add_attributes(vec![
-        attr("action", "buy"),
+        attr("action", action),
         attr("in_amount", in_amount_pre_fee.to_string()),
         attr("out_amount", out_amount.to_string()),
+        attr("fee_pct", fee_pct.to_string()),
+        attr("fee_asset", fee_asset_key(fee_asset)),
     ]);
 
-    // Add submsg to send platform fee if exists
-    if let Some(submg) = build_fee_transfer_submsg(deps.storage, &quote_token, fee_amount)? {
-        resp = resp.add_submessage(submg);
+    // Pay platform fee to its recipients, and the referrer its share
+    resp = resp.add_submessages(distribute_fee(store, &base_token, fee_asset, fee_amount)?);
+
+    if let Some(referrer) = referrer {
+        resp = resp
+            .add_attribute("referrer", referrer.to_string())
+            .add_attribute("referral_fee_amount", referral_fee_amount.to_string());
+        if fee_asset == FeeAsset::Base && !referral_fee_amount.is_zero() {
+            resp = resp.add_submessage(base_token.transfer(referrer, referral_fee_amount)?);
+        }
+    }
+
+    // Add submsg to return any unspent quote tokens to initiator
+    if !refund_amount.is_zero() {
+        resp = resp
+            .add_attribute("refund_amount", refund_amount.to_string())
+            .add_submessage(quote_token.transfer(initiator, refund_amount)?);
     }
 
     // Add submsg to send purchased base tokens to initiator
-    Ok(resp.add_submessage(base_token.transfer(&initiator, out_amount)?))
+    Ok(resp.add_submessage(base_token.transfer(initiator, out_amount)?))
 }
 
-pub fn exec_sell(
-    ctx: Context,
-    msg: SellMsg,
-    amount: Uint128,
+/// Persist the curve and stats after a sell and build the response
+fn settle_sell(
+    store: &mut dyn Storage,
+    env: &Env,
+    initiator: &Addr,
+    referrer: Option<&Addr>,
+    curve: &Curve,
+    action: &str,
+    quote: SwapQuote,
 ) -> Result<Response, ContractError> {
-    let Context { deps, info, env } = ctx;
-    let SellMsg {
-        initiator,
-        min_out_amount,
-    } = msg;
+    let SwapQuote {
+        in_amount,
+        out_amount,
+        fee_amount,
+        fee_pct,
+        fee_asset,
+        lp_fee_amount,
+        refund_amount,
+        ..
+    } = quote;
 
-    let mut curve = CURVE.load(deps.storage)?;
-    let quote_token = QUOTE_TOKEN.load(deps.storage)?;
+    let quote_token = QUOTE_TOKEN.load(store)?;
+    let base_token = BASE_TOKEN.load(store)?;
 
-    // Get amount to swap in
-    let in_amount = amount;
+    // Quote value of the sell before fees
+    let gross_out_amount = match fee_asset {
+        FeeAsset::Quote => add_u128(out_amount, fee_amount)?,
+        FeeAsset::Base => out_amount,
+    };
 
-    // Perform CP AMM swap
-    let out_amount_pre_fee = curve.sell(in_amount, min_out_amount)?;
+    // The LP portion of the fee stays in the curve's reserves
+    let fee_amount = record_lp_fee(store, fee_amount, lp_fee_amount)?;
 
-    // Compute sell-side platform fee
-    let fee_amount = mul_pct_u128(out_amount_pre_fee, FEE_PCT_SELL.load(deps.storage)?)?;
+    CURVE.save(store, curve)?;
 
     // Increment total historical aggregate fee amount
-    NET_MAKER_FEE.update(deps.storage, |n| -> Result<_, ContractError> {
-        add_u128(n, fee_amount)
-    })?;
-
-    // Subtract fee from amount recieved by sender
-    let out_amount = sub_u128(out_amount_pre_fee, fee_amount)?;
+    NET_MAKER_FEE.update(store, |n| n.add(fee_asset, fee_amount))?;
 
-    // Get initiator. The initiator is either the user performing the tx or user
-    // on whose behalf the operator is performing it.
-    let initiator = resolve_swap_initiator(deps.storage, deps.api, &info.sender, true, initiator, "sell")?;
+    // Credit the referrer with the swap and carve its share out of the fee
+    let referral_fee_amount = match referrer {
+        Some(referrer) => record_referral(store, referrer, initiator, gross_out_amount, fee_amount, fee_asset)?,
+        None => Uint128::zero(),
+    };
+    let fee_amount = sub_u128(fee_amount, referral_fee_amount)?;
 
     // Update initiator's account info
-    ACCOUNT_STATS.update(deps.storage, &initiator, |maybe_stats| -> Result<_, ContractError> {
+    record_daily_volume(store, initiator, env.block.time, gross_out_amount)?;
+    ACCOUNT_STATS.update(store, initiator, |maybe_stats| -> Result<_, ContractError> {
         let mut stats = maybe_stats.unwrap_or_default();
         stats.n_sells = add_u32(stats.n_sells, 1)?;
-        stats.net_base_in = add_u128(stats.net_base_in, in_amount)?;
-        stats.net_quote_out = add_u128(stats.net_quote_out, out_amount)?;
+        stats.net_base_in = add_u256(stats.net_base_in, in_amount)?;
+        stats.net_quote_out = add_u256(stats.net_quote_out, out_amount)?;
         Ok(stats)
     })?;
 
     // Update global stats
-    MAKER_STATS.update(deps.storage, |mut stats| -> Result<_, ContractError> {
+    MAKER_STATS.update(store, |mut stats| -> Result<_, ContractError> {
         stats.n = add_u64(stats.n, 1u64)?;
         if stats.max.is_none() || stats.max.clone().and_then(|m| Some(in_amount > m.amount)).unwrap() {
             stats.max = Some(MaxSwapInfo {
@@ -165,38 +384,550 @@ pub fn exec_sell(
         Ok(stats)
     })?;
 
-    // Update candlestick data
+    // Update candlestick data, with the base sold as base volume and the quote
+    // paid out as quote volume
     OhlcBar::upsert(
-        deps.storage,
+        store,
         env.block.time,
         curve.calculate_quote_price()?,
-        out_amount,
         in_amount,
+        out_amount,
     )?;
 
     let mut resp = Response::new().add_attributes(vec![
-        attr("action", "sell"),
+        attr("action", action),
         attr("in_amount", in_amount.to_string()),
         attr("out_amount", out_amount.to_string()),
+        attr("fee_pct", fee_pct.to_string()),
+        attr("fee_asset", fee_asset_key(fee_asset)),
     ]);
 
-    // Add submsg to send platform fee if exists
-    if let Some(submg) = build_fee_transfer_submsg(deps.storage, &quote_token, fee_amount)? {
-        resp = resp.add_submessage(submg);
+    // Pay platform fee to its recipients, and the referrer its share
+    resp = resp.add_submessages(distribute_fee(store, &base_token, fee_asset, fee_amount)?);
+
+    if let Some(referrer) = referrer {
+        resp = resp
+            .add_attribute("referrer", referrer.to_string())
+            .add_attribute("referral_fee_amount", referral_fee_amount.to_string());
+        if fee_asset == FeeAsset::Base && !referral_fee_amount.is_zero() {
+            resp = resp.add_submessage(base_token.transfer(referrer, referral_fee_amount)?);
+        }
+    }
+
+    // Add submsg to return any unspent base tokens to initiator
+    if !refund_amount.is_zero() {
+        resp = resp
+            .add_attribute("refund_amount", refund_amount.to_string())
+            .add_submessage(base_token.transfer(initiator, refund_amount)?);
     }
 
     // Add submsg to send purchased quote tokens to initiator
-    Ok(resp.add_submessage(quote_token.transfer(&initiator, out_amount)?))
+    Ok(resp.add_submessage(quote_token.transfer(initiator, out_amount)?))
 }
 
-fn build_fee_transfer_submsg(
-    store: &dyn Storage,
-    quote_token: &Token,
+/// Adds the LP portion of a fee, already deposited into the curve, to the
+/// total retained and returns the remaining portion to be sent to the fee
+/// recipient.
+fn record_lp_fee(
+    store: &mut dyn Storage,
     fee_amount: Uint128,
-) -> Result<Option<SubMsg>, ContractError> {
-    if !fee_amount.is_zero() {
-        let fee_recipient = FEE_ADDR.load(store)?;
-        return Ok(Some(quote_token.transfer(&fee_recipient, fee_amount)?));
+    lp_fee_amount: Uint128,
+) -> Result<Uint128, ContractError> {
+    if !lp_fee_amount.is_zero() {
+        LP_FEE_RETAINED.update(store, |n| -> Result<_, ContractError> {
+            add_u256(n, lp_fee_amount)
+        })?;
+    }
+    sub_u128(fee_amount, lp_fee_amount)
+}
+
+/// Validates the referrer of a swap, if any, which can't be the initiator
+fn resolve_referrer(
+    api: &dyn Api,
+    initiator: &Addr,
+    maybe_referrer: Option<Addr>,
+) -> Result<Option<Addr>, ContractError> {
+    if let Some(referrer) = maybe_referrer {
+        let referrer = api.addr_validate(referrer.as_str())?;
+        if referrer == *initiator {
+            return Err(ContractError::ValidationError {
+                reason: "swap initiator cannot refer itself".to_owned(),
+            });
+        }
+        return Ok(Some(referrer));
     }
     Ok(None)
 }
+
+/// Credits the referrer with a swap's quote volume and its share of the fee,
+/// which is returned. A quote share accrues to the referrer, whereas a base
+/// share is left for the caller to send.
+fn record_referral(
+    store: &mut dyn Storage,
+    referrer: &Addr,
+    initiator: &Addr,
+    volume: Uint128,
+    fee_amount: Uint128,
+    fee_asset: FeeAsset,
+) -> Result<Uint128, ContractError> {
+    let referral_fee_amount = mul_pct_u128(fee_amount, REFERRAL_FEE_PCT.load(store)?)?;
+
+    let is_new_referee = !REFEREES.has(store, (referrer, initiator));
+    if is_new_referee {
+        REFEREES.save(store, (referrer, initiator), &true)?;
+    }
+
+    REFERRER_STATS.update(store, referrer, |maybe_stats| -> Result<_, ContractError> {
+        let mut stats = maybe_stats.unwrap_or_default();
+        if is_new_referee {
+            stats.n_referees = add_u32(stats.n_referees, 1)?;
+        }
+        stats.volume = add_u256(stats.volume, volume)?;
+        match fee_asset {
+            FeeAsset::Quote => stats.fees_earned = add_u256(stats.fees_earned, referral_fee_amount)?,
+            FeeAsset::Base => stats.base_fees_earned = add_u256(stats.base_fees_earned, referral_fee_amount)?,
+        }
+        Ok(stats)
+    })?;
+
+    if fee_asset == FeeAsset::Quote {
+        accrue_fee(store, referrer, referral_fee_amount)?;
+    }
+
+    Ok(referral_fee_amount)
+}
+
+/// Amount of quote sent in for a buy. If amount is None, it implies that the
+/// quote token is a native coin in info.funds; otherwise, it's a CW20.
+fn resolve_quote_in_amount(
+    quote_token: &Token,
+    info: &MessageInfo,
+    amount: Option<Uint128>,
+) -> Result<Uint128, ContractError> {
+    if let Some(amount) = amount {
+        Ok(amount)
+    } else if let Some(coin) = quote_token.find_in_funds(&info.funds, None) {
+        Ok(coin.amount)
+    } else {
+        Err(ContractError::MissingFunds {
+            denom: quote_token.to_key(),
+        })
+    }
+}
+
+/// Fails if the price moved by more than the max price impact over a swap
+fn ensure_price_impact(
+    quote: &SwapQuote,
+    max_price_impact_bps: Option<u32>,
+) -> Result<(), ContractError> {
+    if let Some(max_impact_bps) = max_price_impact_bps {
+        let impact = quote.calculate_price_impact()?;
+        let impact_bps = to_u128(mul_dec256(impact, Decimal256::from_ratio(10_000u128, 1u128))?.to_uint_floor())?;
+        if impact_bps > Uint128::from(max_impact_bps) {
+            return Err(ContractError::PriceImpactTooHigh {
+                impact_bps: impact_bps.u128(),
+                max_impact_bps,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Splits the fee between the fee recipients by weight. Quote fees accrue to
+/// each recipient, to be claimed later, whereas base fees are sent right away.
+/// Any remainder left by rounding goes to the fee addr.
+fn distribute_fee(
+    store: &mut dyn Storage,
+    base_token: &Token,
+    fee_asset: FeeAsset,
+    fee_amount: Uint128,
+) -> Result<Vec<SubMsg>, ContractError> {
+    if fee_amount.is_zero() {
+        return Ok(vec![]);
+    }
+
+    let fee_addr = FEE_ADDR.load(store)?;
+    let recipients = FEE_RECIPIENTS.load(store)?;
+    let total_weight = recipients.iter().map(|r| r.weight as u128).sum::<u128>();
+
+    let mut amounts: Vec<(Addr, Uint128)> = Vec::with_capacity(recipients.len());
+    let mut remainder = fee_amount;
+    for recipient in recipients.iter() {
+        let amount = mul_ratio_u128(fee_amount, recipient.weight as u128, total_weight)?;
+        remainder = sub_u128(remainder, amount)?;
+        amounts.push((recipient.address.to_owned(), amount));
+    }
+    if let Some((_, amount)) = amounts.iter_mut().find(|(addr, _)| *addr == fee_addr) {
+        *amount = add_u128(*amount, remainder)?;
+    }
+
+    let mut submsgs: Vec<SubMsg> = vec![];
+    for (recipient, amount) in amounts {
+        if amount.is_zero() {
+            continue;
+        }
+        let totals = match fee_asset {
+            FeeAsset::Quote => FEE_RECIPIENT_TOTALS,
+            FeeAsset::Base => FEE_RECIPIENT_BASE_TOTALS,
+        };
+        totals.update(store, &recipient, |n| -> Result<_, ContractError> {
+            add_u256(n.unwrap_or_default(), amount)
+        })?;
+        match fee_asset {
+            FeeAsset::Quote => accrue_fee(store, &recipient, amount)?,
+            FeeAsset::Base => submsgs.push(base_token.transfer(&recipient, amount)?),
+        }
+    }
+
+    Ok(submsgs)
+}
+
+fn fee_asset_key(fee_asset: FeeAsset) -> &'static str {
+    match fee_asset {
+        FeeAsset::Quote => "quote",
+        FeeAsset::Base => "base",
+    }
+}
+
+/// Adds to the fees claimable by the given address
+fn accrue_fee(
+    store: &mut dyn Storage,
+    addr: &Addr,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    if amount.is_zero() {
+        return Ok(());
+    }
+    CLAIMABLE_FEES.update(store, addr, |n| -> Result<_, ContractError> {
+        add_u128(n.unwrap_or_default(), amount)
+    })?;
+    Ok(())
+}
+
+#[cfg(all(test, not(feature = "library")))]
+mod tests {
+    use cosmwasm_std::{coins, testing::mock_env, Uint256};
+
+    use super::*;
+    use crate::{
+        curve::CurveKind,
+        models::referral::ReferrerStats,
+        msg::{Cw20ReceiveInnerMsg, ExecuteMsg, InstantiateMsg, QueryMsg, ReferrerResponse},
+        quote::Quoter,
+        state::OHLC_BARS,
+        testing::{
+            attr, buy, buy_msg, claimable, execute_as, fee_preview, instantiate_msg, overview, query_as, receive, sell,
+            sell_msg, setup, setup_fee_split, setup_with, total_paid, TestDeps, BASE, QUOTE_DENOM, TRADER,
+        },
+    };
+
+    /// Default curve that pays referrers 20% of the fee, with no LP fee
+    fn setup_referrals(fee_asset: FeeAsset) -> TestDeps {
+        setup_with(InstantiateMsg {
+            lp_fee_pct: None,
+            referral_fee_pct: Some(Uint128::new(200_000)),
+            taker_fee_asset: Some(fee_asset),
+            maker_fee_asset: Some(fee_asset),
+            ..instantiate_msg(30_000_000_000)
+        })
+    }
+
+    fn buy_referred(
+        deps: &mut TestDeps,
+        sender: &str,
+        amount: u128,
+        referrer: &str,
+    ) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::Buy(BuyMsg {
+            referrer: Some(Addr::unchecked(referrer)),
+            ..buy_msg()
+        });
+        execute_as(deps, sender, &coins(amount, QUOTE_DENOM), msg)
+    }
+
+    fn referrer_stats(
+        deps: &TestDeps,
+        address: &str,
+    ) -> ReferrerStats {
+        let msg = QueryMsg::Referrer {
+            address: Addr::unchecked(address),
+        };
+        query_as::<ReferrerResponse>(deps, msg).stats
+    }
+
+    #[test]
+    fn pays_referrer_a_share_of_quote_fees() {
+        let mut deps = setup_referrals(FeeAsset::Quote);
+
+        // 20% of the 10k fee accrues to the referrer and the rest to the fee
+        // recipient
+        let resp = buy_referred(&mut deps, TRADER, 1_000_000, "ref").unwrap();
+        assert_eq!(attr(&resp, "referral_fee_amount"), Uint128::new(2_000));
+        assert_eq!(claimable(&deps, "ref"), Uint128::new(2_000));
+        assert_eq!(claimable(&deps, "fees"), Uint128::new(8_000));
+        assert_eq!(total_paid(&deps), vec![("fees".to_owned(), Uint256::from(8_000u128))]);
+
+        // Exact-out swaps credit the referrer too, with the quote value of the
+        // swap before fees as volume
+        let msg = ExecuteMsg::BuyExactOut(BuyExactOutMsg {
+            initiator: None,
+            referrer: Some(Addr::unchecked("ref")),
+            out_amount: Uint128::new(1_000_000_000),
+            max_in_amount: None,
+            max_price_impact_bps: None,
+        });
+        let resp = execute_as(&mut deps, TRADER, &coins(1_000_000, QUOTE_DENOM), msg).unwrap();
+        let exact_in = attr(&resp, "in_amount");
+        let exact_referral = attr(&resp, "referral_fee_amount");
+        assert!(!exact_referral.is_zero());
+
+        let sell_quote = Quoter::new(&overview(&deps))
+            .unwrap()
+            .sell_exact_out(Uint128::new(100_000), None)
+            .unwrap();
+        let msg = Cw20ReceiveInnerMsg::SellExactOut(SellExactOutMsg {
+            initiator: None,
+            referrer: Some(Addr::unchecked("ref")),
+            out_amount: Uint128::new(100_000),
+            max_in_amount: None,
+            max_price_impact_bps: None,
+        });
+        let resp = receive(&mut deps, TRADER, 10_000_000_000, msg).unwrap();
+        let sell_referral = attr(&resp, "referral_fee_amount");
+        assert_eq!(
+            sell_referral,
+            mul_pct_u128(sell_quote.fee_amount, Uint128::new(200_000)).unwrap()
+        );
+
+        let stats = referrer_stats(&deps, "ref");
+        assert_eq!(stats.n_referees, 1);
+        assert_eq!(
+            stats.fees_earned,
+            Uint256::from(2_000 + exact_referral.u128() + sell_referral.u128())
+        );
+        assert_eq!(
+            claimable(&deps, "ref"),
+            Uint128::new(2_000) + exact_referral + sell_referral
+        );
+        let sell_volume = sell_quote.out_amount + sell_quote.fee_amount;
+        assert_eq!(
+            stats.volume,
+            Uint256::from(1_000_000 + exact_in.u128() + sell_volume.u128())
+        );
+        assert_eq!(stats.base_fees_earned, Uint256::zero());
+    }
+
+    #[test]
+    fn counts_each_referee_once() {
+        let mut deps = setup_referrals(FeeAsset::Quote);
+        buy_referred(&mut deps, TRADER, 1_000_000, "ref").unwrap();
+        buy_referred(&mut deps, TRADER, 3_000_000, "ref").unwrap();
+        assert_eq!(referrer_stats(&deps, "ref").n_referees, 1);
+        buy_referred(&mut deps, "other", 1_000_000, "ref").unwrap();
+
+        let stats = referrer_stats(&deps, "ref");
+        assert_eq!(stats.n_referees, 2);
+        assert_eq!(stats.volume, Uint256::from(5_000_000u128));
+        assert_eq!(stats.fees_earned, Uint256::from(10_000u128));
+        assert_eq!(referrer_stats(&deps, "nobody"), ReferrerStats::default());
+    }
+
+    #[test]
+    fn rejects_self_referral() {
+        let mut deps = setup_referrals(FeeAsset::Quote);
+        let result = buy_referred(&mut deps, TRADER, 1_000_000, TRADER);
+        assert!(matches!(result, Err(ContractError::ValidationError { .. })));
+    }
+
+    #[test]
+    fn sends_referrer_its_share_of_base_fees() {
+        let mut deps = setup_referrals(FeeAsset::Base);
+        let quote = Quoter::new(&overview(&deps))
+            .unwrap()
+            .with_fee_preview(&fee_preview(&deps, TRADER))
+            .buy(Uint128::new(1_000_000), None)
+            .unwrap();
+        let referral_fee_amount = mul_pct_u128(quote.fee_amount, Uint128::new(200_000)).unwrap();
+        assert!(!referral_fee_amount.is_zero());
+
+        let resp = buy_referred(&mut deps, TRADER, 1_000_000, "ref").unwrap();
+        let base_token = Token::Address(Addr::unchecked(BASE));
+        assert!(resp.messages.contains(
+            &base_token
+                .transfer(&Addr::unchecked("ref"), referral_fee_amount)
+                .unwrap()
+        ));
+        assert_eq!(claimable(&deps, "ref"), Uint128::zero());
+
+        let stats = referrer_stats(&deps, "ref");
+        assert_eq!(stats.base_fees_earned, Uint256::from(referral_fee_amount));
+        assert_eq!(stats.fees_earned, Uint256::zero());
+        assert_eq!(stats.volume, Uint256::from(1_000_000u128));
+    }
+
+    #[test]
+    fn splits_fee_by_weight() {
+        let mut deps = setup_fee_split();
+
+        // A 10k fee splits 3,333 to 6,666, with the leftover unit going to the
+        // fee addr
+        buy(&mut deps, 1_000_000);
+        assert_eq!(
+            total_paid(&deps),
+            vec![
+                ("fees".to_owned(), Uint256::from(3_334u128)),
+                ("dev".to_owned(), Uint256::from(6_666u128))
+            ]
+        );
+
+        buy(&mut deps, 3_000_000);
+        assert_eq!(
+            total_paid(&deps),
+            vec![
+                ("fees".to_owned(), Uint256::from(13_334u128)),
+                ("dev".to_owned(), Uint256::from(26_666u128))
+            ]
+        );
+    }
+
+    /// Price impact of the quote in whole basis points, as the contract
+    /// measures it
+    fn impact_bps(quote: &SwapQuote) -> u32 {
+        let impact = quote.calculate_price_impact().unwrap() * Decimal256::from_ratio(10_000u128, 1u128);
+        impact.to_uint_floor().to_string().parse().unwrap()
+    }
+
+    fn assert_impact_too_high(
+        result: Result<Response, ContractError>,
+        expected_impact_bps: u32,
+    ) {
+        match result {
+            Err(ContractError::PriceImpactTooHigh {
+                impact_bps,
+                max_impact_bps,
+            }) => {
+                assert_eq!(impact_bps, expected_impact_bps as u128);
+                assert_eq!(max_impact_bps, expected_impact_bps - 1);
+            },
+            result => panic!("expected PriceImpactTooHigh, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn enforces_max_price_impact_on_buys() {
+        let mut deps = setup(CurveKind::ConstantProduct {}, 30_000_000_000);
+        let quoter = Quoter::new(&overview(&deps)).unwrap();
+        let buy_with_max = |deps: &mut TestDeps, max_price_impact_bps| {
+            let msg = ExecuteMsg::Buy(BuyMsg {
+                max_price_impact_bps: Some(max_price_impact_bps),
+                ..buy_msg()
+            });
+            execute_as(deps, TRADER, &coins(1_000_000_000, QUOTE_DENOM), msg)
+        };
+
+        // Rejected just under the actual impact, which the error reports, and
+        // allowed right at it
+        let bps = impact_bps(&quoter.clone().buy(Uint128::new(1_000_000_000), None).unwrap());
+        assert!(bps > 100);
+        assert_impact_too_high(buy_with_max(&mut deps, bps - 1), bps);
+        buy_with_max(&mut deps, bps).unwrap();
+
+        let buy_exact_out = |deps: &mut TestDeps, max_price_impact_bps| {
+            let msg = ExecuteMsg::BuyExactOut(BuyExactOutMsg {
+                initiator: None,
+                referrer: None,
+                out_amount: Uint128::new(10_000_000_000_000),
+                max_in_amount: None,
+                max_price_impact_bps: Some(max_price_impact_bps),
+            });
+            execute_as(deps, TRADER, &coins(1_000_000_000, QUOTE_DENOM), msg)
+        };
+        let quoter = Quoter::new(&overview(&deps)).unwrap();
+        let bps = impact_bps(
+            &quoter
+                .clone()
+                .buy_exact_out(Uint128::new(10_000_000_000_000), None)
+                .unwrap(),
+        );
+        assert_impact_too_high(buy_exact_out(&mut deps, bps - 1), bps);
+        buy_exact_out(&mut deps, bps).unwrap();
+    }
+
+    #[test]
+    fn enforces_max_price_impact_on_sells() {
+        let mut deps = setup(CurveKind::ConstantProduct {}, 30_000_000_000);
+        buy(&mut deps, 10_000_000_000);
+
+        let sell_with_max = |deps: &mut TestDeps, max_price_impact_bps| {
+            let msg = Cw20ReceiveInnerMsg::Sell(SellMsg {
+                max_price_impact_bps: Some(max_price_impact_bps),
+                ..sell_msg()
+            });
+            receive(deps, TRADER, 20_000_000_000_000, msg)
+        };
+        let quoter = Quoter::new(&overview(&deps)).unwrap();
+        let bps = impact_bps(&quoter.clone().sell(Uint128::new(20_000_000_000_000), None).unwrap());
+        assert!(bps > 100);
+        assert_impact_too_high(sell_with_max(&mut deps, bps - 1), bps);
+        sell_with_max(&mut deps, bps).unwrap();
+
+        let sell_exact_out = |deps: &mut TestDeps, max_price_impact_bps| {
+            let msg = Cw20ReceiveInnerMsg::SellExactOut(SellExactOutMsg {
+                initiator: None,
+                referrer: None,
+                out_amount: Uint128::new(1_000_000_000),
+                max_in_amount: None,
+                max_price_impact_bps: Some(max_price_impact_bps),
+            });
+            receive(deps, TRADER, 100_000_000_000_000, msg)
+        };
+        let quoter = Quoter::new(&overview(&deps)).unwrap();
+        let bps = impact_bps(
+            &quoter
+                .clone()
+                .sell_exact_out(Uint128::new(1_000_000_000), None)
+                .unwrap(),
+        );
+        assert_impact_too_high(sell_exact_out(&mut deps, bps - 1), bps);
+        sell_exact_out(&mut deps, bps).unwrap();
+    }
+
+    #[test]
+    fn round_trip_leaves_no_quote_behind() {
+        let mut deps = setup(
+            CurveKind::Linear {
+                start_price: Decimal256::from_ratio(3u128, 100_000u128),
+                end_price: Decimal256::from_ratio(3u128, 1_000u128),
+            },
+            0,
+        );
+        let buy_out = attr(&buy(&mut deps, 1_000_000_000), "out_amount");
+        let sell_out = attr(&sell(&mut deps, buy_out.u128()), "out_amount");
+
+        // Everything the buyer paid goes back to them or to the fee recipient,
+        // save for a little rounding in the pool's favor
+        let overview = overview(&deps);
+        let fee_amount = overview.stats.net_taker_fee.quote + overview.stats.net_maker_fee.quote;
+        assert_eq!(overview.amm.base_reserve, overview.amm.base_supply);
+        assert_eq!(overview.amm.lp_fee_retained, Uint256::zero());
+        assert_eq!(total_paid(&deps), vec![("fees".to_owned(), fee_amount)]);
+        assert_eq!(
+            Uint256::from(sell_out) + fee_amount + overview.amm.quote_reserve_real,
+            Uint256::from(1_000_000_000u128)
+        );
+        assert!(overview.amm.quote_reserve_real < Uint256::from(10u128));
+    }
+
+    #[test]
+    fn records_base_and_quote_volume_by_side() {
+        let mut deps = setup_fee_split();
+        let buy_out = attr(&buy(&mut deps, 1_000_000), "out_amount");
+        let sell_out = attr(&sell(&mut deps, 1_000_000_000), "out_amount");
+
+        // The buy put 990k quote into the curve net of its fee
+        let seconds = mock_env().block.time.seconds();
+        let bar = OHLC_BARS.load(deps.as_ref().storage, seconds - seconds % 60).unwrap();
+        assert_eq!(bar.vb, Uint256::from(buy_out.u128() + 1_000_000_000));
+        assert_eq!(bar.vq, Uint256::from(990_000 + sell_out.u128()));
+    }
+}
@@ -0,0 +1,92 @@
+use cosmwasm_std::{attr, Response};
+
+use crate::{
+    error::ContractError,
+    state::{CLAIMABLE_FEES, QUOTE_TOKEN},
+};
+
+use super::Context;
+
+/// Sends the sender all the quote fees accrued to it so far
+pub fn exec_claim_fees(ctx: Context) -> Result<Response, ContractError> {
+    let Context { deps, info, .. } = ctx;
+
+    let amount = CLAIMABLE_FEES.may_load(deps.storage, &info.sender)?.unwrap_or_default();
+    if amount.is_zero() {
+        return Err(ContractError::ValidationError {
+            reason: format!("no fees to claim for {}", info.sender),
+        });
+    }
+
+    CLAIMABLE_FEES.remove(deps.storage, &info.sender);
+
+    let quote_token = QUOTE_TOKEN.load(deps.storage)?;
+
+    Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "claim_fees"),
+            attr("recipient", info.sender.to_string()),
+            attr("amount", amount.to_string()),
+        ])
+        .add_submessage(quote_token.transfer(&info.sender, amount)?))
+}
+
+#[cfg(all(test, not(feature = "library")))]
+mod tests {
+    use cosmwasm_std::{coins, BankMsg, CosmosMsg, SubMsg, Uint128, Uint256};
+
+    use super::*;
+    use crate::{
+        msg::ExecuteMsg,
+        quote::Quoter,
+        testing::{buy, claimable, execute_as, overview, sell, setup_fee_split, total_paid, TestDeps, TRADER},
+    };
+
+    fn claim_fees(
+        deps: &mut TestDeps,
+        sender: &str,
+    ) -> Result<Response, ContractError> {
+        execute_as(deps, sender, &[], ExecuteMsg::ClaimFees {})
+    }
+
+    #[test]
+    fn accrues_fees_until_claimed() {
+        let mut deps = setup_fee_split();
+
+        // Quote fees accrue rather than being sent with the swap
+        let resp = buy(&mut deps, 1_000_000);
+        assert!(resp.messages.iter().all(|m| !matches!(
+            &m.msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address, .. }) if to_address == "fees" || to_address == "dev"
+        )));
+        let quote = Quoter::new(&overview(&deps))
+            .unwrap()
+            .sell(Uint128::new(1_000_000_000), None)
+            .unwrap();
+        sell(&mut deps, 1_000_000_000);
+        let dev_amount = 6_666 + quote.fee_amount.u128() * 2 / 3;
+        let fees_amount = 3_334 + quote.fee_amount.u128() - quote.fee_amount.u128() * 2 / 3;
+        assert_eq!(claimable(&deps, "dev"), Uint128::new(dev_amount));
+        assert_eq!(claimable(&deps, "fees"), Uint128::new(fees_amount));
+
+        // Claiming sends the whole balance and zeroes it, leaving the other
+        // recipient's balance and the totals paid as they were
+        let resp = claim_fees(&mut deps, "dev").unwrap();
+        assert_eq!(
+            resp.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "dev".to_owned(),
+                amount: coins(dev_amount, "uquote"),
+            })]
+        );
+        assert_eq!(claimable(&deps, "dev"), Uint128::zero());
+        assert_eq!(claimable(&deps, "fees"), Uint128::new(fees_amount));
+        assert_eq!(total_paid(&deps)[1], ("dev".to_owned(), Uint256::from(dev_amount)));
+        assert!(claim_fees(&mut deps, "dev").is_err());
+        assert!(claim_fees(&mut deps, TRADER).is_err());
+
+        // Later fees accrue afresh
+        buy(&mut deps, 3_000_000);
+        assert_eq!(claimable(&deps, "dev"), Uint128::new(20_000));
+    }
+}
@@ -8,7 +8,7 @@ use cosmwasm_std::{ensure_eq, from_json, Addr, Response};
 use cw20::Cw20ReceiveMsg;
 
 use super::{
-    buy_sell::{exec_buy, exec_sell},
+    buy_sell::{exec_buy, exec_buy_exact_out, exec_sell, exec_sell_exact_out},
     Context,
 };
 
@@ -54,6 +54,21 @@ pub fn exec_cw20_receive(
             }
             exec_sell(ctx, msg, amount)
         },
+        Cw20ReceiveInnerMsg::BuyExactOut(mut msg) => {
+            ensure_is_authorized_cw20(&quote_token, &ctx.info.sender)?;
+            if !using_operator {
+                msg.initiator = Some(token_sender);
+            }
+            exec_buy_exact_out(ctx, msg, Some(amount))
+        },
+        Cw20ReceiveInnerMsg::SellExactOut(mut msg) => {
+            let base_token = BASE_TOKEN.load(ctx.deps.storage)?;
+            ensure_is_authorized_cw20(&base_token, &ctx.info.sender)?;
+            if !using_operator {
+                msg.initiator = Some(token_sender);
+            }
+            exec_sell_exact_out(ctx, msg, amount)
+        },
     }
 }
 
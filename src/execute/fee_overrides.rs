@@ -0,0 +1,98 @@
+use cosmwasm_std::{attr, Response};
+
+use crate::{error::ContractError, msg::SetFeeOverrideMsg, state::FEE_OVERRIDES, utils::ensure_admin};
+
+use super::Context;
+
+/// Sets or removes the fee charged to a particular swap initiator
+pub fn exec_set_fee_override(
+    ctx: Context,
+    msg: SetFeeOverrideMsg,
+) -> Result<Response, ContractError> {
+    let Context { deps, info, .. } = ctx;
+    let SetFeeOverrideMsg { address, fee_override } = msg;
+
+    ensure_admin(deps.storage, &info.sender, "set fee overrides")?;
+
+    let address = deps.api.addr_validate(address.as_str())?;
+
+    if let Some(fee_override) = &fee_override {
+        fee_override.validate()?;
+        FEE_OVERRIDES.save(deps.storage, &address, fee_override)?;
+    } else {
+        FEE_OVERRIDES.remove(deps.storage, &address);
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "set_fee_override"),
+        attr("address", address.to_string()),
+        attr(
+            "fee_pct",
+            fee_override
+                .map(|o| o.fee_pct().to_string())
+                .unwrap_or_else(|| "none".to_owned()),
+        ),
+    ]))
+}
+
+#[cfg(all(test, not(feature = "library")))]
+mod tests {
+    use cosmwasm_std::{Addr, Uint128};
+
+    use crate::{
+        curve::CurveKind,
+        error::ContractError,
+        models::fee::FeeOverride,
+        msg::{ExecuteMsg, SetFeeOverrideMsg},
+        testing::{attr, buy, claimable, execute_as, fee_preview, setup, TestDeps, ADMIN, TRADER},
+    };
+
+    fn set_fee_override(
+        deps: &mut TestDeps,
+        sender: &str,
+        fee_override: Option<FeeOverride>,
+    ) -> Result<(), ContractError> {
+        let msg = ExecuteMsg::SetFeeOverride(SetFeeOverrideMsg {
+            address: Addr::unchecked(TRADER),
+            fee_override,
+        });
+        execute_as(deps, sender, &[], msg).map(|_| ())
+    }
+
+    #[test]
+    fn only_admin_sets_fee_overrides() {
+        let mut deps = setup(CurveKind::ConstantProduct {}, 30_000_000_000);
+        let result = set_fee_override(&mut deps, TRADER, Some(FeeOverride::Exempt {}));
+        assert!(matches!(
+            result,
+            Err(ContractError::NotAuthorized { reason })
+                if reason == format!("only admin {} is authorized to set fee overrides", ADMIN)
+        ));
+        assert_eq!(fee_preview(&deps, TRADER).fee_override, None);
+    }
+
+    #[test]
+    fn exempt_initiator_pays_no_fee() {
+        let mut deps = setup(CurveKind::ConstantProduct {}, 30_000_000_000);
+        set_fee_override(&mut deps, ADMIN, Some(FeeOverride::Exempt {})).unwrap();
+
+        let resp = buy(&mut deps, 1_000_000_000);
+        assert_eq!(attr(&resp, "fee_pct"), Uint128::zero());
+        assert_eq!(claimable(&deps, "fees"), Uint128::zero());
+    }
+
+    #[test]
+    fn removing_override_restores_regular_fee() {
+        let mut deps = setup(CurveKind::ConstantProduct {}, 30_000_000_000);
+        let custom = FeeOverride::Custom {
+            pct: Uint128::new(1_000),
+        };
+        set_fee_override(&mut deps, ADMIN, Some(custom.clone())).unwrap();
+        assert_eq!(fee_preview(&deps, TRADER).fee_override, Some(custom));
+        assert_eq!(attr(&buy(&mut deps, 1_000_000_000), "fee_pct"), Uint128::new(1_000));
+
+        set_fee_override(&mut deps, ADMIN, None).unwrap();
+        assert_eq!(fee_preview(&deps, TRADER).fee_override, None);
+        assert_eq!(attr(&buy(&mut deps, 1_000_000_000), "fee_pct"), Uint128::new(10_000));
+    }
+}
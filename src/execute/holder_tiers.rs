@@ -0,0 +1,108 @@
+use cosmwasm_std::{attr, Response};
+
+use crate::{error::ContractError, models::fee::HolderTierConfig, state::HOLDER_TIERS, utils::ensure_admin};
+
+use super::Context;
+
+/// Replaces the holder tier table, or removes it if None
+pub fn exec_set_holder_tiers(
+    ctx: Context,
+    config: Option<HolderTierConfig>,
+) -> Result<Response, ContractError> {
+    let Context { deps, env, info } = ctx;
+
+    ensure_admin(deps.storage, &info.sender, "set holder tiers")?;
+
+    let n_tiers = if let Some(config) = &config {
+        config.validate(deps.as_ref(), &env.contract.address)?;
+        HOLDER_TIERS.save(deps.storage, config)?;
+        config.tiers.len()
+    } else {
+        HOLDER_TIERS.remove(deps.storage);
+        0
+    };
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "set_holder_tiers"),
+        attr("n_tiers", n_tiers.to_string()),
+    ]))
+}
+
+#[cfg(all(test, not(feature = "library")))]
+mod tests {
+    use cosmwasm_std::{coins, to_json_binary, Addr, ContractResult, SystemResult, Uint128};
+    use cw20::BalanceResponse;
+
+    use super::*;
+    use crate::{
+        curve::CurveKind,
+        models::fee::{FeeTierKind, HolderTier},
+        msg::ExecuteMsg,
+        testing::{attr, buy, execute_as, fee_preview, setup, TestDeps, ADMIN, TRADER},
+        token::Token,
+    };
+
+    fn set_holder_tiers(
+        deps: &mut TestDeps,
+        token: Token,
+    ) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::SetHolderTiers(Some(HolderTierConfig {
+            token: Some(token),
+            tiers: vec![
+                HolderTier {
+                    min_balance: Uint128::new(1_000),
+                    discount_pct: Uint128::new(100_000),
+                },
+                HolderTier {
+                    min_balance: Uint128::new(10_000),
+                    discount_pct: Uint128::new(500_000),
+                },
+            ],
+        }));
+        execute_as(deps, ADMIN, &[], msg)
+    }
+
+    #[test]
+    fn holder_tier_discounts_fees() {
+        let mut deps = setup(CurveKind::ConstantProduct {}, 30_000_000_000);
+        set_holder_tiers(&mut deps, Token::Denom("uhold".to_owned())).unwrap();
+
+        deps.querier.update_balance(TRADER, coins(999, "uhold"));
+        let preview = fee_preview(&deps, TRADER);
+        assert_eq!(
+            (preview.taker_pct, preview.maker_pct),
+            (Uint128::new(10_000), Uint128::new(20_000))
+        );
+        assert_eq!(preview.applied_tier, None);
+
+        deps.querier.update_balance(TRADER, coins(5_000, "uhold"));
+        let preview = fee_preview(&deps, TRADER);
+        assert_eq!(
+            (preview.taker_pct, preview.maker_pct),
+            (Uint128::new(9_000), Uint128::new(18_000))
+        );
+        assert_eq!(preview.holder_tier.unwrap().min_balance, Uint128::new(1_000));
+        assert_eq!(preview.applied_tier, Some(FeeTierKind::Holder));
+
+        deps.querier.update_balance(TRADER, coins(10_000, "uhold"));
+        assert_eq!(fee_preview(&deps, TRADER).taker_pct, Uint128::new(5_000));
+        assert_eq!(attr(&buy(&mut deps, 1_000_000), "fee_pct"), Uint128::new(5_000));
+    }
+
+    #[test]
+    fn rejects_holder_token_without_balances() {
+        let mut deps = setup(CurveKind::ConstantProduct {}, 30_000_000_000);
+        assert!(set_holder_tiers(&mut deps, Token::Address(Addr::unchecked("Not A Token"))).is_err());
+        assert!(set_holder_tiers(&mut deps, Token::Address(Addr::unchecked("hold"))).is_err());
+
+        deps.querier.update_wasm(|_| {
+            SystemResult::Ok(ContractResult::Ok(
+                to_json_binary(&BalanceResponse {
+                    balance: Uint128::zero(),
+                })
+                .unwrap(),
+            ))
+        });
+        set_holder_tiers(&mut deps, Token::Address(Addr::unchecked("hold"))).unwrap();
+    }
+}
@@ -1,7 +1,11 @@
 pub mod buy_sell;
+pub mod claim_fees;
 pub mod cw20_receive;
+pub mod fee_overrides;
+pub mod holder_tiers;
 pub mod on_balance_change;
 pub mod set_config;
+pub mod volume_tiers;
 
 use cosmwasm_std::{DepsMut, Env, MessageInfo};
 
@@ -1,10 +1,10 @@
 use crate::{
     error::ContractError,
-    math::{mul_ratio_u128, sub_u128},
+    math::{mul_ratio_u256, sub_u256},
     msg::{BalanceChangeEvent, BalanceChangeMsg},
     state::{ACCOUNT_STATS, BASE_TOKEN, CURVE},
 };
-use cosmwasm_std::{attr, ensure_eq, Addr, Response, Storage, Uint128};
+use cosmwasm_std::{attr, ensure_eq, Addr, Response, Storage, Uint128, Uint256};
 
 use super::Context;
 
@@ -58,10 +58,10 @@ pub fn exec_on_balance_change(
 
 pub fn calc_avg_cost_basis(
     quote_decimals: u8,
-    total_cost: Uint128,
+    total_cost: Uint256,
     total_base_amount: Uint128,
-) -> Result<Uint128, ContractError> {
-    mul_ratio_u128(total_cost, 10u128.pow(quote_decimals as u32), total_base_amount)
+) -> Result<Uint256, ContractError> {
+    mul_ratio_u256(total_cost, 10u128.pow(quote_decimals as u32), total_base_amount)
 }
 
 pub fn update_initiator_total_cost(
@@ -74,11 +74,11 @@ pub fn update_initiator_total_cost(
     ACCOUNT_STATS.update(store, initiator, |maybe_stats| -> Result<_, ContractError> {
         let mut initator_stats = maybe_stats.unwrap_or_default();
         if delta.is_zero() {
-            initator_stats.total_cost = Uint128::zero();
+            initator_stats.total_cost = Uint256::zero();
         } else {
             let cost_basis = calc_avg_cost_basis(quote_decimals, initator_stats.total_cost, initiator_balance)?;
-            let cost_of_tokens_sold = mul_ratio_u128(delta, cost_basis, 10u128.pow(quote_decimals as u32))?;
-            initator_stats.total_cost = sub_u128(initator_stats.total_cost, cost_of_tokens_sold)?;
+            let cost_of_tokens_sold = mul_ratio_u256(delta, cost_basis, 10u128.pow(quote_decimals as u32))?;
+            initator_stats.total_cost = sub_u256(initator_stats.total_cost, cost_of_tokens_sold)?;
         }
         Ok(initator_stats)
     })?;
@@ -92,7 +92,7 @@ pub fn update_recipient_total_cost(
 ) -> Result<(), ContractError> {
     ACCOUNT_STATS.update(store, &recipient, |maybe_stats| -> Result<_, ContractError> {
         let mut recipient_stats = maybe_stats.unwrap_or_default();
-        recipient_stats.total_cost = sub_u128(recipient_stats.total_cost, delta)?;
+        recipient_stats.total_cost = sub_u256(recipient_stats.total_cost, delta)?;
         Ok(recipient_stats)
     })?;
     Ok(())
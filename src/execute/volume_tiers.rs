@@ -0,0 +1,29 @@
+use cosmwasm_std::{attr, Response};
+
+use crate::{error::ContractError, models::fee::VolumeTier, state::VOLUME_TIERS, utils::ensure_admin};
+
+use super::Context;
+
+/// Replaces the volume tier table, or removes it if None
+pub fn exec_set_volume_tiers(
+    ctx: Context,
+    tiers: Option<Vec<VolumeTier>>,
+) -> Result<Response, ContractError> {
+    let Context { deps, info, .. } = ctx;
+
+    ensure_admin(deps.storage, &info.sender, "set volume tiers")?;
+
+    let n_tiers = if let Some(tiers) = &tiers {
+        VolumeTier::validate_tiers(tiers)?;
+        VOLUME_TIERS.save(deps.storage, tiers)?;
+        tiers.len()
+    } else {
+        VOLUME_TIERS.remove(deps.storage);
+        0
+    };
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "set_volume_tiers"),
+        attr("n_tiers", n_tiers.to_string()),
+    ]))
+}
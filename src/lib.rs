@@ -2,20 +2,22 @@
 pub mod contract;
 pub mod curve;
 pub mod error;
-#[cfg(not(feature = "library"))]
 pub mod execute;
 mod math;
 pub mod models;
 pub mod msg;
 #[cfg(not(feature = "library"))]
 pub mod query;
+pub mod quote;
 pub mod state;
+#[cfg(all(test, not(feature = "library")))]
+mod testing;
 pub mod token;
 mod utils;
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(not(any(target_arch = "wasm32", feature = "library")))]
 mod interface;
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(not(any(target_arch = "wasm32", feature = "library")))]
 pub use crate::interface::Contract;
 #[cfg(not(target_arch = "wasm32"))]
 pub use crate::msg::{ExecuteMsgFns as ContractExecuteMsgFns, QueryMsgFns as ContractQueryMsgFns};
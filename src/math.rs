@@ -1,4 +1,4 @@
-use cosmwasm_std::{OverflowError, OverflowOperation, StdError, Uint128, Uint256, Uint64};
+use cosmwasm_std::{Decimal256, OverflowError, OverflowOperation, StdError, Uint128, Uint256, Uint64};
 
 use crate::error::ContractError;
 
@@ -39,6 +39,20 @@ pub fn div_u256<A: Into<Uint256>, B: Into<Uint256>>(
         .map_err(|e| ContractError::Std(StdError::divide_by_zero(e)))
 }
 
+pub fn div_ceil_u256<A: Into<Uint256>, B: Into<Uint256>>(
+    numerator: A,
+    denominator: B,
+) -> Result<Uint256, ContractError> {
+    let a: Uint256 = numerator.into();
+    let b: Uint256 = denominator.into();
+    let quotient = div_u256(a, b)?;
+    if mul_u256(quotient, b)? < a {
+        add_u256(quotient, Uint256::one())
+    } else {
+        Ok(quotient)
+    }
+}
+
 pub fn add_u128<A: Into<Uint128>, B: Into<Uint128>>(
     a: A,
     b: B,
@@ -88,6 +102,29 @@ pub fn mul_ratio_u128<A: Into<Uint128>, B: Into<Uint128>, C: Into<Uint128>>(
         .map_err(|e| ContractError::Std(StdError::generic_err(e.to_string())))
 }
 
+pub fn mul_ratio_ceil_u128<A: Into<Uint128>, B: Into<Uint128>, C: Into<Uint128>>(
+    base: A,
+    numerator: B,
+    denominator: C,
+) -> Result<Uint128, ContractError> {
+    let a: Uint128 = base.into();
+    let b: Uint128 = numerator.into();
+    let c: Uint128 = denominator.into();
+    to_u128(div_ceil_u256(mul_u256(a, b)?, c)?)
+}
+
+pub fn mul_ratio_u256<A: Into<Uint256>, B: Into<Uint256>, C: Into<Uint256>>(
+    base: A,
+    numerator: B,
+    denominator: C,
+) -> Result<Uint256, ContractError> {
+    let a: Uint256 = base.into();
+    let b: Uint256 = numerator.into();
+    let c: Uint256 = denominator.into();
+    a.checked_multiply_ratio(b, c)
+        .map_err(|e| ContractError::Std(StdError::generic_err(e.to_string())))
+}
+
 pub fn mul_pct_u128<A: Into<Uint128>, B: Into<Uint128>>(
     base: A,
     numerator: B,
@@ -97,6 +134,23 @@ pub fn mul_pct_u128<A: Into<Uint128>, B: Into<Uint128>>(
     mul_ratio_u128(a, b, 1_000_000u128)
 }
 
+/// Returns the smallest amount which, after subtracting `pct` of it, leaves at
+/// least `base`, along with the amount subtracted.
+pub fn gross_up_pct_u128<A: Into<Uint128>, B: Into<Uint128>>(
+    base: A,
+    pct: B,
+) -> Result<(Uint128, Uint128), ContractError> {
+    let a: Uint128 = base.into();
+    let b: Uint128 = pct.into();
+    if b >= Uint128::from(1_000_000u128) {
+        return Err(ContractError::ValidationError {
+            reason: "cannot gross up a 100% fee".to_owned(),
+        });
+    }
+    let gross = mul_ratio_ceil_u128(a, 1_000_000u128, sub_u128(1_000_000u128, b)?)?;
+    Ok((gross, sub_u128(gross, a)?))
+}
+
 pub fn add_u64<A: Into<Uint64>, B: Into<Uint64>>(
     a: A,
     b: B,
@@ -116,3 +170,69 @@ pub fn add_u32(
         })
     })
 }
+
+pub fn to_u128<A: Into<Uint256>>(a: A) -> Result<Uint128, ContractError> {
+    let a: Uint256 = a.into();
+    Ok(a.try_into()?)
+}
+
+/// Approximates e^x via its Taylor series. Every term is rounded down, so the
+/// result is monotonic in x, which is all the curve math needs from it.
+pub fn exp_dec256(x: Decimal256) -> Result<Decimal256, ContractError> {
+    let mut sum = Decimal256::one();
+    let mut term = Decimal256::one();
+    for i in 1..=200u128 {
+        term = div_dec256(mul_dec256(term, x)?, Decimal256::from_ratio(i, 1u128))?;
+        if term.is_zero() {
+            break;
+        }
+        sum = add_dec256(sum, term)?;
+    }
+    Ok(sum)
+}
+
+pub fn from_ratio_dec256<A: Into<Uint256>, B: Into<Uint256>>(
+    numerator: A,
+    denominator: B,
+) -> Result<Decimal256, ContractError> {
+    Decimal256::checked_from_ratio(numerator, denominator)
+        .map_err(|e| ContractError::Std(StdError::generic_err(e.to_string())))
+}
+
+pub fn add_dec256(
+    a: Decimal256,
+    b: Decimal256,
+) -> Result<Decimal256, ContractError> {
+    a.checked_add(b).map_err(|e| ContractError::Std(StdError::overflow(e)))
+}
+
+pub fn sub_dec256(
+    a: Decimal256,
+    b: Decimal256,
+) -> Result<Decimal256, ContractError> {
+    a.checked_sub(b).map_err(|e| ContractError::Std(StdError::overflow(e)))
+}
+
+pub fn mul_dec256(
+    a: Decimal256,
+    b: Decimal256,
+) -> Result<Decimal256, ContractError> {
+    a.checked_mul(b).map_err(|e| ContractError::Std(StdError::overflow(e)))
+}
+
+pub fn div_dec256(
+    numerator: Decimal256,
+    denominator: Decimal256,
+) -> Result<Decimal256, ContractError> {
+    numerator
+        .checked_div(denominator)
+        .map_err(|e| ContractError::Std(StdError::generic_err(e.to_string())))
+}
+
+pub fn pow_dec256(
+    base: Decimal256,
+    exp: u32,
+) -> Result<Decimal256, ContractError> {
+    base.checked_pow(exp)
+        .map_err(|e| ContractError::Std(StdError::overflow(e)))
+}
@@ -1,5 +1,13 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Timestamp, Uint128, Uint64};
+use cosmwasm_std::{Addr, Order, Storage, Timestamp, Uint128, Uint256, Uint64};
+use cw_storage_plus::Bound;
+
+use crate::{error::ContractError, math::add_u256, state::ACCOUNT_DAILY_VOLUME};
+
+/// Number of days of daily volume summed into an account's rolling volume
+pub const VOLUME_WINDOW_DAYS: u64 = 30;
+
+const SECONDS_PER_DAY: u64 = 86_400;
 
 #[cw_serde]
 pub struct Account {
@@ -11,11 +19,11 @@ pub struct Account {
 pub struct AccountStats {
     pub n_buys: u32,
     pub n_sells: u32,
-    pub total_cost: Uint128,
-    pub net_quote_in: Uint128,
-    pub net_quote_out: Uint128,
-    pub net_base_in: Uint128,
-    pub net_base_out: Uint128,
+    pub total_cost: Uint256,
+    pub net_quote_in: Uint256,
+    pub net_quote_out: Uint256,
+    pub net_base_in: Uint256,
+    pub net_base_out: Uint256,
 }
 
 #[cw_serde]
@@ -31,3 +39,177 @@ pub struct SwapStats {
     pub n: Uint64,
     pub max: Option<MaxSwapInfo>,
 }
+
+/// Adds quote volume to the account's bucket for the current day, dropping
+/// buckets that have aged out of the rolling window.
+pub fn record_daily_volume(
+    store: &mut dyn Storage,
+    account: &Addr,
+    time: Timestamp,
+    volume: Uint128,
+) -> Result<(), ContractError> {
+    let day = time.seconds() / SECONDS_PER_DAY;
+    let stale_days = ACCOUNT_DAILY_VOLUME
+        .prefix(account)
+        .keys(
+            store,
+            None,
+            Some(Bound::exclusive(window_start_day(day))),
+            Order::Ascending,
+        )
+        .collect::<Result<Vec<_>, _>>()?;
+    for stale_day in stale_days {
+        ACCOUNT_DAILY_VOLUME.remove(store, (account, stale_day));
+    }
+    ACCOUNT_DAILY_VOLUME.update(store, (account, day), |n| -> Result<_, ContractError> {
+        add_u256(n.unwrap_or_default(), volume)
+    })?;
+    Ok(())
+}
+
+/// Quote volume traded by the account over the last `VOLUME_WINDOW_DAYS`
+/// days, including today
+pub fn calculate_rolling_volume(
+    store: &dyn Storage,
+    account: &Addr,
+    time: Timestamp,
+) -> Result<Uint256, ContractError> {
+    let day = time.seconds() / SECONDS_PER_DAY;
+    let mut volume = Uint256::zero();
+    for result in ACCOUNT_DAILY_VOLUME.prefix(account).range(
+        store,
+        Some(Bound::inclusive(window_start_day(day))),
+        None,
+        Order::Ascending,
+    ) {
+        let (_, day_volume) = result?;
+        volume = add_u256(volume, day_volume)?;
+    }
+    Ok(volume)
+}
+
+fn window_start_day(day: u64) -> u64 {
+    day.saturating_sub(VOLUME_WINDOW_DAYS - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::MockStorage;
+
+    use super::*;
+    use crate::{models::fee::VolumeTier, state::VOLUME_TIERS, utils::resolve_volume_tier};
+
+    const DAY_100: u64 = 100 * SECONDS_PER_DAY;
+
+    fn day_keys(
+        store: &dyn Storage,
+        account: &Addr,
+    ) -> Vec<u64> {
+        ACCOUNT_DAILY_VOLUME
+            .prefix(account)
+            .keys(store, None, None, Order::Ascending)
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn buckets_volume_by_day() {
+        let mut store = MockStorage::new();
+        let account = Addr::unchecked("trader");
+        let record = |store: &mut MockStorage, seconds, volume| {
+            record_daily_volume(store, &account, Timestamp::from_seconds(seconds), Uint128::new(volume)).unwrap()
+        };
+
+        record(&mut store, DAY_100, 100);
+        record(&mut store, DAY_100 + SECONDS_PER_DAY - 1, 200);
+        record(&mut store, DAY_100 + SECONDS_PER_DAY, 400);
+
+        assert_eq!(day_keys(&store, &account), vec![100, 101]);
+        assert_eq!(
+            ACCOUNT_DAILY_VOLUME.load(&store, (&account, 100)).unwrap(),
+            Uint256::from(300u128)
+        );
+        assert_eq!(
+            calculate_rolling_volume(&store, &account, Timestamp::from_seconds(DAY_100 + SECONDS_PER_DAY)).unwrap(),
+            Uint256::from(700u128)
+        );
+    }
+
+    #[test]
+    fn drops_volume_after_window() {
+        let mut store = MockStorage::new();
+        let account = Addr::unchecked("trader");
+        record_daily_volume(
+            &mut store,
+            &account,
+            Timestamp::from_seconds(DAY_100),
+            Uint128::new(100),
+        )
+        .unwrap();
+
+        // Still counted on the window's last day, gone the day after
+        let last_day = Timestamp::from_seconds(DAY_100 + (VOLUME_WINDOW_DAYS - 1) * SECONDS_PER_DAY);
+        assert_eq!(
+            calculate_rolling_volume(&store, &account, last_day).unwrap(),
+            Uint256::from(100u128)
+        );
+        let aged_out = last_day.plus_seconds(SECONDS_PER_DAY);
+        assert_eq!(
+            calculate_rolling_volume(&store, &account, aged_out).unwrap(),
+            Uint256::zero()
+        );
+
+        // Recording on the window's last day keeps the old bucket, whereas
+        // recording once it has aged out prunes it
+        record_daily_volume(&mut store, &account, last_day, Uint128::new(1)).unwrap();
+        assert_eq!(day_keys(&store, &account), vec![100, 129]);
+        record_daily_volume(&mut store, &account, aged_out, Uint128::new(1)).unwrap();
+        assert_eq!(day_keys(&store, &account), vec![129, 130]);
+        assert_eq!(
+            calculate_rolling_volume(&store, &account, aged_out).unwrap(),
+            Uint256::from(2u128)
+        );
+    }
+
+    #[test]
+    fn selects_highest_volume_tier_met() {
+        let mut store = MockStorage::new();
+        let account = Addr::unchecked("trader");
+        let now = Timestamp::from_seconds(DAY_100);
+        VOLUME_TIERS
+            .save(
+                &mut store,
+                &vec![
+                    VolumeTier {
+                        min_volume: Uint256::from(1_000u128),
+                        discount_pct: Uint128::new(100_000),
+                    },
+                    VolumeTier {
+                        min_volume: Uint256::from(5_000u128),
+                        discount_pct: Uint128::new(250_000),
+                    },
+                ],
+            )
+            .unwrap();
+        let discount = |store: &MockStorage| {
+            resolve_volume_tier(store, now, Some(&account))
+                .unwrap()
+                .map(|t| t.discount_pct)
+        };
+
+        assert_eq!(discount(&store), None);
+        record_daily_volume(
+            &mut store,
+            &account,
+            now.minus_seconds(SECONDS_PER_DAY),
+            Uint128::new(999),
+        )
+        .unwrap();
+        assert_eq!(discount(&store), None);
+        record_daily_volume(&mut store, &account, now, Uint128::new(1)).unwrap();
+        assert_eq!(discount(&store), Some(Uint128::new(100_000)));
+        record_daily_volume(&mut store, &account, now, Uint128::new(4_000)).unwrap();
+        assert_eq!(discount(&store), Some(Uint128::new(250_000)));
+        assert_eq!(resolve_volume_tier(&store, now, None).unwrap(), None);
+    }
+}
@@ -0,0 +1,390 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Deps, Uint128, Uint256};
+
+use crate::{
+    error::ContractError,
+    math::{add_u128, add_u256, mul_ratio_u128, sub_u128},
+    token::Token,
+};
+
+/// Largest number of recipients that a fee can be split between
+pub const MAX_FEE_RECIPIENTS: usize = 10;
+
+/// Largest number of tiers in a holder or volume tier table
+pub const MAX_HOLDER_TIERS: usize = 10;
+pub const MAX_VOLUME_TIERS: usize = 10;
+
+/// Longest window of recent price action a dynamic fee can look back over
+pub const MAX_DYNAMIC_FEE_WINDOW_MINUTES: u32 = 60;
+
+/// Asset in which a side's fee is charged
+#[cw_serde]
+#[derive(Copy, Default)]
+pub enum FeeAsset {
+    /// Taken out of the quote paid for buys or received for sells
+    #[default]
+    Quote,
+    /// Taken out of the base received for buys or paid for sells
+    Base,
+}
+
+/// Running total of fees charged in each asset
+#[cw_serde]
+#[derive(Default)]
+pub struct NetFee {
+    pub quote: Uint256,
+    pub base: Uint256,
+}
+
+impl NetFee {
+    pub fn add(
+        mut self,
+        fee_asset: FeeAsset,
+        amount: Uint128,
+    ) -> Result<Self, ContractError> {
+        match fee_asset {
+            FeeAsset::Quote => self.quote = add_u256(self.quote, amount)?,
+            FeeAsset::Base => self.base = add_u256(self.base, amount)?,
+        }
+        Ok(self)
+    }
+}
+
+/// Recipient of a share of each fee, proportional to its weight
+#[cw_serde]
+pub struct FeeRecipient {
+    pub address: Addr,
+    pub weight: u32,
+}
+
+/// Fee charged to a particular swap initiator in place of the regular fees
+#[cw_serde]
+pub enum FeeOverride {
+    /// Pays no fees at all
+    Exempt {},
+    /// Pays the given pct on both sides, scaled by 1e6
+    Custom { pct: Uint128 },
+}
+
+impl FeeOverride {
+    pub fn validate(&self) -> Result<(), ContractError> {
+        if let FeeOverride::Custom { pct } = self {
+            if *pct > Uint128::new(1_000_000) {
+                return Err(ContractError::ValidationError {
+                    reason: "custom fee pct cannot exceed 100%".to_owned(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn fee_pct(&self) -> Uint128 {
+        match self {
+            FeeOverride::Exempt {} => Uint128::zero(),
+            FeeOverride::Custom { pct } => *pct,
+        }
+    }
+}
+
+/// Discount off the fee for accounts holding at least `min_balance` of the
+/// tier token
+#[cw_serde]
+pub struct HolderTier {
+    pub min_balance: Uint128,
+    /// Portion of the fee waived, scaled by 1e6
+    pub discount_pct: Uint128,
+}
+
+/// Table of fee discounts by holdings of some token
+#[cw_serde]
+pub struct HolderTierConfig {
+    /// Token whose balance places an account in a tier. Defaults to the base
+    /// token.
+    pub token: Option<Token>,
+    /// Tiers in ascending order of min balance
+    pub tiers: Vec<HolderTier>,
+}
+
+impl HolderTierConfig {
+    /// Checks the tiers, and that the tier token answers balance queries,
+    /// since every swap queries it
+    pub fn validate(
+        &self,
+        deps: Deps,
+        contract_addr: &Addr,
+    ) -> Result<(), ContractError> {
+        if self.tiers.is_empty() || self.tiers.len() > MAX_HOLDER_TIERS {
+            return Err(ContractError::ValidationError {
+                reason: format!("holder tier count must be between 1 and {}", MAX_HOLDER_TIERS),
+            });
+        }
+        for (i, tier) in self.tiers.iter().enumerate() {
+            if tier.discount_pct > Uint128::new(1_000_000) {
+                return Err(ContractError::ValidationError {
+                    reason: "holder tier discount cannot exceed 100%".to_owned(),
+                });
+            }
+            if i > 0 && tier.min_balance <= self.tiers[i - 1].min_balance {
+                return Err(ContractError::ValidationError {
+                    reason: "holder tiers must be in ascending order of min balance".to_owned(),
+                });
+            }
+        }
+        if let Some(token) = &self.token {
+            if let Token::Address(address) = token {
+                deps.api.addr_validate(address.as_str())?;
+            }
+            token.query_balance(deps.querier, contract_addr)?;
+        }
+        Ok(())
+    }
+}
+
+/// Discount off the fee for accounts that traded at least `min_volume` quote
+/// over the rolling volume window
+#[cw_serde]
+pub struct VolumeTier {
+    pub min_volume: Uint256,
+    /// Portion of the fee waived, scaled by 1e6
+    pub discount_pct: Uint128,
+}
+
+impl VolumeTier {
+    /// Checks a tier table, which must be in ascending order of min volume
+    pub fn validate_tiers(tiers: &[VolumeTier]) -> Result<(), ContractError> {
+        if tiers.is_empty() || tiers.len() > MAX_VOLUME_TIERS {
+            return Err(ContractError::ValidationError {
+                reason: format!("volume tier count must be between 1 and {}", MAX_VOLUME_TIERS),
+            });
+        }
+        for (i, tier) in tiers.iter().enumerate() {
+            if tier.discount_pct > Uint128::new(1_000_000) {
+                return Err(ContractError::ValidationError {
+                    reason: "volume tier discount cannot exceed 100%".to_owned(),
+                });
+            }
+            if i > 0 && tier.min_volume <= tiers[i - 1].min_volume {
+                return Err(ContractError::ValidationError {
+                    reason: "volume tiers must be in ascending order of min volume".to_owned(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Kind of tier that an account's fee discount comes from
+#[cw_serde]
+#[derive(Copy)]
+pub enum FeeTierKind {
+    Holder,
+    Volume,
+}
+
+/// Fee that rises with recent volatility, charged on both sides in place of
+/// the flat taker and maker fees
+#[cw_serde]
+pub struct DynamicFeeConfig {
+    /// Minutes of recent OHLC bars over which the price range is measured
+    pub window_minutes: u32,
+    /// Fee charged while the price is flat, scaled by 1e6
+    pub min_pct: Uint128,
+    /// Fee charged once the price range reaches `max_range_bps`, scaled by 1e6
+    pub max_pct: Uint128,
+    /// Price range, from low to high, in basis points at which the fee maxes out
+    pub max_range_bps: u32,
+}
+
+impl DynamicFeeConfig {
+    pub fn validate(&self) -> Result<(), ContractError> {
+        if self.window_minutes == 0 || self.window_minutes > MAX_DYNAMIC_FEE_WINDOW_MINUTES {
+            return Err(ContractError::ValidationError {
+                reason: format!(
+                    "dynamic fee window must be between 1 and {} minutes",
+                    MAX_DYNAMIC_FEE_WINDOW_MINUTES
+                ),
+            });
+        }
+        if self.min_pct > self.max_pct || self.max_pct > Uint128::new(1_000_000) {
+            return Err(ContractError::ValidationError {
+                reason: "dynamic fee min pct must not exceed its max pct, nor its max pct 100%".to_owned(),
+            });
+        }
+        if self.max_range_bps == 0 {
+            return Err(ContractError::ValidationError {
+                reason: "dynamic fee max range must be nonzero".to_owned(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Fee pct for the given price range, scaling linearly from the min pct up
+    /// to the max pct
+    pub fn calculate_fee_pct(
+        &self,
+        range_bps: Uint128,
+    ) -> Result<Uint128, ContractError> {
+        let range_bps = range_bps.min(self.max_range_bps.into());
+        let spread = sub_u128(self.max_pct, self.min_pct)?;
+        add_u128(self.min_pct, mul_ratio_u128(spread, range_bps, self.max_range_bps)?)
+    }
+}
+
+/// How a launch fee falls from its starting pct to the regular taker fee
+#[cw_serde]
+pub enum LaunchFeeDecay {
+    /// Falls in a straight line over the schedule's duration
+    Linear,
+    /// Halves every `half_life_seconds`, interpolating linearly between
+    /// halvings. Whatever would be left at the end of the schedule's duration
+    /// is phased out linearly over it, so the fee still ends on the regular fee.
+    Exponential { half_life_seconds: u64 },
+}
+
+/// Elevated taker fee charged right after launch to discourage sniping
+#[cw_serde]
+pub struct LaunchFeeSchedule {
+    /// Taker fee at launch, scaled by 1e6
+    pub start_pct: Uint128,
+    /// Seconds after launch at which the regular taker fee takes over
+    pub duration_seconds: u64,
+    pub decay: LaunchFeeDecay,
+}
+
+impl LaunchFeeSchedule {
+    pub fn validate(&self) -> Result<(), ContractError> {
+        if self.duration_seconds == 0 || matches!(self.decay, LaunchFeeDecay::Exponential { half_life_seconds: 0 }) {
+            return Err(ContractError::ValidationError {
+                reason: "launch fee duration and half-life must be nonzero".to_owned(),
+            });
+        }
+        if self.start_pct > Uint128::new(1_000_000) {
+            return Err(ContractError::ValidationError {
+                reason: "launch fee start pct must not exceed 100%".to_owned(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Taker fee pct a number of seconds after launch, decaying from the start
+    /// pct down to the given base pct
+    pub fn calculate_fee_pct(
+        &self,
+        base_pct: Uint128,
+        elapsed_seconds: u64,
+    ) -> Result<Uint128, ContractError> {
+        if elapsed_seconds >= self.duration_seconds || self.start_pct <= base_pct {
+            return Ok(base_pct);
+        }
+
+        let excess = sub_u128(self.start_pct, base_pct)?;
+        let remaining = match self.decay {
+            LaunchFeeDecay::Linear => {
+                mul_ratio_u128(excess, self.duration_seconds - elapsed_seconds, self.duration_seconds)?
+            },
+            LaunchFeeDecay::Exponential { half_life_seconds } => {
+                let decayed = halve_over(excess, half_life_seconds, elapsed_seconds)?;
+                let residual = halve_over(excess, half_life_seconds, self.duration_seconds)?;
+                sub_u128(
+                    decayed,
+                    mul_ratio_u128(residual, elapsed_seconds, self.duration_seconds)?,
+                )?
+            },
+        };
+
+        add_u128(base_pct, remaining)
+    }
+}
+
+/// Halves the amount every `half_life_seconds`, interpolating linearly between
+/// halvings
+fn halve_over(
+    amount: Uint128,
+    half_life_seconds: u64,
+    elapsed_seconds: u64,
+) -> Result<Uint128, ContractError> {
+    let halvings = elapsed_seconds / half_life_seconds;
+    if halvings >= u128::BITS as u64 {
+        return Ok(Uint128::zero());
+    }
+    let halved = Uint128::new(amount.u128() >> halvings);
+    let partial = elapsed_seconds % half_life_seconds;
+    sub_u128(halved, mul_ratio_u128(halved, partial, 2 * half_life_seconds)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn launch_fee(decay: LaunchFeeDecay) -> LaunchFeeSchedule {
+        LaunchFeeSchedule {
+            start_pct: Uint128::new(500_000),
+            duration_seconds: 7_200,
+            decay,
+        }
+    }
+
+    #[test]
+    fn linear_launch_fee_decays_to_base_fee() {
+        let schedule = launch_fee(LaunchFeeDecay::Linear);
+        let base_pct = Uint128::new(10_000);
+        let fee_at = |t| schedule.calculate_fee_pct(base_pct, t).unwrap();
+
+        assert_eq!(fee_at(0), Uint128::new(500_000));
+        assert_eq!(fee_at(3_600), Uint128::new(255_000));
+        assert_eq!(fee_at(7_199), Uint128::new(10_068));
+        assert_eq!(fee_at(7_200), base_pct);
+        assert_eq!(fee_at(100_000), base_pct);
+    }
+
+    #[test]
+    fn exponential_launch_fee_decays_to_base_fee() {
+        let schedule = launch_fee(LaunchFeeDecay::Exponential {
+            half_life_seconds: 3_600,
+        });
+        let base_pct = Uint128::new(10_000);
+        let fee_at = |t| schedule.calculate_fee_pct(base_pct, t).unwrap();
+
+        assert_eq!(fee_at(0), Uint128::new(500_000));
+        // The halving leaves 245k of the 490k excess, less half the 122.5k
+        // that would be left at the end of the duration
+        assert_eq!(fee_at(3_600), Uint128::new(193_750));
+        // No jump as the duration runs out
+        assert!(fee_at(7_199) - base_pct < Uint128::new(100));
+        assert_eq!(fee_at(7_200), base_pct);
+
+        let mut prev = fee_at(0);
+        for t in (60..=7_200).step_by(60) {
+            let fee = fee_at(t);
+            assert!(fee <= prev);
+            prev = fee;
+        }
+    }
+
+    #[test]
+    fn launch_fee_never_undercuts_base_fee() {
+        let schedule = launch_fee(LaunchFeeDecay::Linear);
+        let base_pct = Uint128::new(600_000);
+        assert_eq!(schedule.calculate_fee_pct(base_pct, 0).unwrap(), base_pct);
+    }
+
+    #[test]
+    fn validates_launch_fee() {
+        assert!(launch_fee(LaunchFeeDecay::Linear).validate().is_ok());
+        assert!(LaunchFeeSchedule {
+            duration_seconds: 0,
+            ..launch_fee(LaunchFeeDecay::Linear)
+        }
+        .validate()
+        .is_err());
+        assert!(launch_fee(LaunchFeeDecay::Exponential { half_life_seconds: 0 })
+            .validate()
+            .is_err());
+        assert!(LaunchFeeSchedule {
+            start_pct: Uint128::new(1_000_001),
+            ..launch_fee(LaunchFeeDecay::Linear)
+        }
+        .validate()
+        .is_err());
+    }
+}
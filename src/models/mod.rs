@@ -1,3 +1,5 @@
 pub mod account;
 pub mod config;
+pub mod fee;
 pub mod ohlc;
+pub mod referral;
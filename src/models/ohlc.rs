@@ -1,20 +1,21 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Storage, Timestamp, Uint128, Uint64};
+use cosmwasm_std::{Decimal256, Order, Storage, Timestamp, Uint128, Uint256, Uint64};
+use cw_storage_plus::Bound;
 
 use crate::{
     error::ContractError,
-    math::{add_u128, add_u32},
+    math::{add_u256, add_u32, div_dec256, mul_dec256, sub_dec256, to_u128},
     state::OHLC_BARS,
 };
 
 #[cw_serde]
 pub struct OhlcBar {
-    pub o: Uint128,
-    pub c: Uint128,
-    pub h: Uint128,
-    pub l: Uint128,
-    pub vb: Uint128,
-    pub vq: Uint128,
+    pub o: Decimal256,
+    pub c: Decimal256,
+    pub h: Decimal256,
+    pub l: Decimal256,
+    pub vb: Uint256,
+    pub vq: Uint256,
     pub t: Uint64,
     pub n: u32,
 }
@@ -22,12 +23,12 @@ pub struct OhlcBar {
 impl OhlcBar {
     pub fn new(t: Uint64) -> Self {
         Self {
-            o: Uint128::zero(),
-            h: Uint128::zero(),
-            l: Uint128::zero(),
-            c: Uint128::zero(),
-            vb: Uint128::zero(),
-            vq: Uint128::zero(),
+            o: Decimal256::zero(),
+            h: Decimal256::zero(),
+            l: Decimal256::zero(),
+            c: Decimal256::zero(),
+            vb: Uint256::zero(),
+            vq: Uint256::zero(),
             n: 0,
             t,
         }
@@ -36,7 +37,7 @@ impl OhlcBar {
     pub fn upsert(
         store: &mut dyn Storage,
         time: Timestamp,
-        price: Uint128,
+        price: Decimal256,
         base_volume: Uint128,
         quote_volume: Uint128,
     ) -> Result<OhlcBar, ContractError> {
@@ -57,10 +58,136 @@ impl OhlcBar {
                 bar.l = price;
             }
             bar.c = price;
-            bar.vq = add_u128(bar.vq, quote_volume)?;
-            bar.vb = add_u128(bar.vb, base_volume)?;
+            bar.vq = add_u256(bar.vq, quote_volume)?;
+            bar.vb = add_u256(bar.vb, base_volume)?;
             bar.n = add_u32(bar.n, 1)?;
             Ok(bar)
         })
     }
+
+    /// Spread between the highest high and lowest low of the bars from the
+    /// given time onward, relative to the low, in basis points
+    pub fn calculate_range_bps(
+        store: &dyn Storage,
+        since: Timestamp,
+    ) -> Result<Uint128, ContractError> {
+        let seconds = since.seconds();
+        let t = seconds - (seconds % 60);
+        let mut range: Option<(Decimal256, Decimal256)> = None;
+        for result in OHLC_BARS.range(store, Some(Bound::inclusive(t)), None, Order::Ascending) {
+            let (_, bar) = result?;
+            range = Some(match range {
+                Some((l, h)) => (l.min(bar.l), h.max(bar.h)),
+                None => (bar.l, bar.h),
+            });
+        }
+        match range {
+            Some((l, h)) if !l.is_zero() => {
+                let range = div_dec256(sub_dec256(h, l)?, l)?;
+                Ok(to_u128(
+                    mul_dec256(range, Decimal256::from_ratio(10_000u128, 1u128))?.to_uint_floor(),
+                )?)
+            },
+            _ => Ok(Uint128::zero()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::MockStorage;
+
+    use super::*;
+    use crate::models::fee::DynamicFeeConfig;
+
+    // Start of a minute bar
+    const T0: u64 = 1_699_999_980;
+
+    fn dynamic_fee() -> DynamicFeeConfig {
+        DynamicFeeConfig {
+            window_minutes: 10,
+            min_pct: Uint128::new(5_000),
+            max_pct: Uint128::new(25_000),
+            max_range_bps: 1_000,
+        }
+    }
+
+    fn upsert(
+        store: &mut MockStorage,
+        seconds: u64,
+        price: Decimal256,
+    ) {
+        OhlcBar::upsert(
+            store,
+            Timestamp::from_seconds(seconds),
+            price,
+            Uint128::one(),
+            Uint128::one(),
+        )
+        .unwrap();
+    }
+
+    fn fee_pct(
+        store: &MockStorage,
+        since: u64,
+    ) -> Uint128 {
+        let range_bps = OhlcBar::calculate_range_bps(store, Timestamp::from_seconds(since)).unwrap();
+        dynamic_fee().calculate_fee_pct(range_bps).unwrap()
+    }
+
+    #[test]
+    fn empty_window_charges_min_fee() {
+        let store = MockStorage::new();
+        assert_eq!(
+            OhlcBar::calculate_range_bps(&store, Timestamp::from_seconds(T0)).unwrap(),
+            Uint128::zero()
+        );
+        assert_eq!(fee_pct(&store, T0), dynamic_fee().min_pct);
+    }
+
+    #[test]
+    fn flat_window_charges_min_fee() {
+        let mut store = MockStorage::new();
+        for i in 0..5 {
+            upsert(&mut store, T0 + i * 45, Decimal256::permille(3));
+        }
+        assert_eq!(fee_pct(&store, T0), dynamic_fee().min_pct);
+    }
+
+    #[test]
+    fn fee_scales_with_range_up_to_max() {
+        let mut store = MockStorage::new();
+
+        // A 5% range is halfway to the max range
+        upsert(&mut store, T0, Decimal256::percent(100));
+        upsert(&mut store, T0 + 30, Decimal256::percent(105));
+        assert_eq!(
+            OhlcBar::calculate_range_bps(&store, Timestamp::from_seconds(T0)).unwrap(),
+            Uint128::new(500)
+        );
+        assert_eq!(fee_pct(&store, T0), Uint128::new(15_000));
+
+        // The max range charges the max fee, as does anything beyond it
+        upsert(&mut store, T0 + 60, Decimal256::percent(110));
+        assert_eq!(fee_pct(&store, T0), dynamic_fee().max_pct);
+        upsert(&mut store, T0 + 120, Decimal256::percent(150));
+        assert_eq!(fee_pct(&store, T0), dynamic_fee().max_pct);
+    }
+
+    #[test]
+    fn ignores_bars_before_window() {
+        let mut store = MockStorage::new();
+        upsert(&mut store, T0, Decimal256::percent(500));
+        upsert(&mut store, T0 + 600, Decimal256::percent(100));
+        upsert(&mut store, T0 + 659, Decimal256::percent(101));
+
+        // Starting midway through a bar still takes in the whole bar
+        let since = T0 + 630;
+        assert_eq!(
+            OhlcBar::calculate_range_bps(&store, Timestamp::from_seconds(since)).unwrap(),
+            Uint128::new(100)
+        );
+        assert_eq!(fee_pct(&store, since), Uint128::new(7_000));
+        assert_eq!(fee_pct(&store, T0 + 660), dynamic_fee().min_pct);
+    }
 }
@@ -0,0 +1,15 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::Uint256;
+
+#[cw_serde]
+#[derive(Default)]
+pub struct ReferrerStats {
+    /// Number of distinct accounts referred
+    pub n_referees: u32,
+    /// Total quote volume of referred swaps
+    pub volume: Uint256,
+    /// Total quote fee amount paid to the referrer
+    pub fees_earned: Uint256,
+    /// Total base fee amount paid to the referrer
+    pub base_fees_earned: Uint256,
+}
@@ -1,11 +1,17 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Uint128, Uint256, Uint64};
+use cosmwasm_std::{Addr, Decimal256, Uint128, Uint256, Uint64};
 use cw20::Cw20ReceiveMsg;
 
 use crate::{
+    curve::{CurveKind, CurveTargets},
     models::{
         account::{AccountStats, SwapStats},
         config::Config,
+        fee::{
+            DynamicFeeConfig, FeeAsset, FeeOverride, FeeRecipient, FeeTierKind, HolderTier, HolderTierConfig,
+            LaunchFeeSchedule, NetFee, VolumeTier,
+        },
+        referral::ReferrerStats,
     },
     token::Token,
 };
@@ -35,15 +41,73 @@ pub struct InstantiateMsg {
     /// swaps on users' behalves.
     pub operator_addr: Option<Addr>,
 
+    /// Manager of fee overrides. Defaults to the instantiator.
+    pub admin_addr: Option<Addr>,
+
     pub base_token: BaseTokenInitArgs,
     pub base_reserve: Uint128,
 
+    /// Virtual base reserve, added to the minted base reserve when pricing the
+    /// curve but never sold.
+    pub base_reserve_virtual: Option<Uint128>,
+
     pub quote_token: QuoteTokenInitArgs,
-    pub quote_reserve: Uint128,
+
+    /// Virtual quote reserve. Required unless the curve is derived from
+    /// `targets` instead.
+    pub quote_reserve: Option<Uint256>,
+
+    /// Launch targets from which to derive the virtual reserves and sellable
+    /// supply of a constant product curve, in place of `quote_reserve` and
+    /// `sellable_supply`. The base reserve is still minted in full.
+    pub targets: Option<CurveTargets>,
+
+    /// Shape of the bonding curve. Defaults to constant product.
+    pub curve_kind: Option<CurveKind>,
+
+    /// Max amount of the base reserve that the curve will sell. Buys beyond it
+    /// are partially filled and refunded. Defaults to the whole reserve.
+    pub sellable_supply: Option<Uint128>,
 
     pub taker_fee_pct: Uint128,
     pub maker_fee_pct: Uint128,
+
+    /// Assets in which taker and maker fees are charged. Default to quote.
+    pub taker_fee_asset: Option<FeeAsset>,
+    pub maker_fee_asset: Option<FeeAsset>,
+
+    /// Portion of taker and maker fees retained in the curve's reserves
+    /// instead of being sent to the fee recipient, scaled by 1e6. Only applies
+    /// to fees charged in quote, and only to a constant product curve, since
+    /// the other kinds never pay the retained quote out to sellers.
+    pub lp_fee_pct: Option<Uint128>,
+
+    /// Share of each fee, after the LP portion, paid to the swap's referrer if
+    /// it has one, scaled by 1e6
+    pub referral_fee_pct: Option<Uint128>,
+
+    /// Elevated taker fee charged right after instantiation, decaying to the
+    /// regular taker fee
+    pub launch_fee: Option<LaunchFeeSchedule>,
+
+    /// Volatility-based fee charged on both sides in place of the taker and
+    /// maker fees
+    pub dynamic_fee: Option<DynamicFeeConfig>,
+
+    /// Fee discounts for accounts holding enough of some token
+    pub holder_tiers: Option<HolderTierConfig>,
+
+    /// Fee discounts by rolling 30-day quote volume, in ascending order of
+    /// min volume. An account gets the better of its holder and volume tiers.
+    pub volume_tiers: Option<Vec<VolumeTier>>,
+
+    /// Platform fee recipient, which also receives any remainder left over by
+    /// rounding the fee split
     pub fee_addr: Addr,
+
+    /// Recipients that split each fee by weight, including `fee_addr`.
+    /// Defaults to `fee_addr` alone.
+    pub fee_recipients: Option<Vec<FeeRecipient>>,
 }
 
 #[cw_serde]
@@ -58,6 +122,11 @@ pub enum ExecuteMsg {
     Receive(Cw20ReceiveMsg),
     OnBalanceChange(BalanceChangeMsg),
     Buy(BuyMsg),
+    BuyExactOut(BuyExactOutMsg),
+    ClaimFees {},
+    SetFeeOverride(SetFeeOverrideMsg),
+    SetHolderTiers(Option<HolderTierConfig>),
+    SetVolumeTiers(Option<Vec<VolumeTier>>),
 }
 
 #[cw_serde]
@@ -71,6 +140,42 @@ pub enum QueryMsg {
 
     #[returns(AccountResponse)]
     Account { address: Addr },
+
+    #[returns(ReferrerResponse)]
+    Referrer { address: Addr },
+
+    #[returns(ClaimableFeesResponse)]
+    ClaimableFees { address: Addr },
+
+    #[returns(FeePreviewResponse)]
+    FeePreview { address: Addr },
+
+    #[returns(FeeOverridesResponse)]
+    FeeOverrides {
+        start_after: Option<Addr>,
+        limit: Option<u16>,
+    },
+
+    #[returns(DepthResponse)]
+    Depth { levels: Option<u16> },
+
+    #[returns(TranchesResponse)]
+    Tranches {},
+
+    #[returns(SimulationResponse)]
+    SimulateBuy { in_amount: Uint128 },
+
+    #[returns(SimulationResponse)]
+    SimulateSell { in_amount: Uint128 },
+
+    #[returns(SimulationResponse)]
+    ReverseSimulateBuy { base_out: Uint128 },
+
+    #[returns(SimulationResponse)]
+    ReverseSimulateSell { quote_out: Uint128 },
+
+    #[returns(SimulateStepsResponse)]
+    SimulateSteps { steps: Vec<SimulationStep> },
 }
 
 #[cw_serde]
@@ -83,27 +188,80 @@ pub struct ConfigResponse(pub Config);
 pub struct CurveFeeOverview {
     pub recipient: Addr,
     pub taker_pct: Uint128,
+    /// Taker fee charged at the current block time, including any launch or
+    /// dynamic fee
+    pub effective_taker_pct: Uint128,
     pub maker_pct: Uint128,
+    /// Maker fee charged at the current block time, including any dynamic fee
+    pub effective_maker_pct: Uint128,
+    pub taker_fee_asset: FeeAsset,
+    pub maker_fee_asset: FeeAsset,
+    /// Portion of each fee retained in the curve's reserves
+    pub lp_pct: Uint128,
+    /// Share of each fee paid to referrers
+    pub referral_pct: Uint128,
+    pub launch_fee: Option<LaunchFeeSchedule>,
+    pub dynamic_fee: Option<DynamicFeeConfig>,
+    pub holder_tiers: Option<HolderTierConfig>,
+    pub volume_tiers: Option<Vec<VolumeTier>>,
+    pub recipients: Vec<FeeRecipientOverview>,
+}
+
+#[cw_serde]
+pub struct FeeRecipientOverview {
+    pub address: Addr,
+    pub weight: u32,
+    /// Total quote fee amount paid to the recipient so far
+    pub total_paid: Uint256,
+    /// Total base fee amount paid to the recipient so far
+    pub total_paid_base: Uint256,
 }
 
 #[cw_serde]
 pub struct CurveStatsOverview {
     pub bids: SwapStats,
     pub asks: SwapStats,
-    pub net_maker_fee: Uint128,
-    pub net_taker_fee: Uint128,
+    /// Fees paid out to fee recipients and referrers, excluding those retained
+    /// by the curve
+    pub net_maker_fee: NetFee,
+    pub net_taker_fee: NetFee,
 }
 
 #[cw_serde]
 pub struct CurveAmmOverview {
+    pub kind: CurveKind,
+    pub quote_price: Decimal256,
+    pub base_price: Decimal256,
     pub base_token: Token,
-    pub base_reserve: Uint128,
+    pub base_reserve: Uint256,
+    pub base_reserve_real: Uint256,
+    pub base_reserve_virtual: Uint256,
+    /// Base reserve at instantiation, i.e. the full supply sold along the curve
+    pub base_supply: Uint256,
+    pub base_sellable_supply: Uint256,
+    pub base_sellable_remaining: Uint256,
     pub base_decimals: u8,
     pub quote_token: Token,
-    pub quote_reserve_real: Uint128,
-    pub quote_reserve_virtual: Uint128,
+    pub quote_reserve_real: Uint256,
+    pub quote_reserve_virtual: Uint256,
     pub quote_decimals: u8,
     pub constant_product: Uint256,
+    pub constant_product_initial: Uint256,
+    /// Ratio of the current to the initial constant product, or none for the
+    /// other curve kinds, whose k never changes and which retain no LP fee.
+    /// k catches up with the reserves whenever an LP fee is retained, taking in
+    /// the fee as well as any gains from rounding in the pool's favor.
+    pub constant_product_growth: Option<Decimal256>,
+    /// Total quote amount of fees retained in the curve's reserves
+    pub lp_fee_retained: Uint256,
+    /// Price at which the curve started selling
+    pub start_price: Decimal256,
+    /// Price at which the curve sells out its sellable supply, or none for a
+    /// constant product curve whose sellable supply is its whole base reserve,
+    /// since its price grows without bound as the reserve runs out
+    pub end_price: Option<Decimal256>,
+    /// Quote value of the initial reserves at the start price
+    pub virtual_liquidity: Uint256,
 }
 
 #[cw_serde]
@@ -116,24 +274,171 @@ pub struct OverviewResponse {
 #[cw_serde]
 pub struct AccountResponse {
     pub stats: AccountStats,
+    /// Quote volume traded over the last 30 days
+    pub volume_30d: Uint256,
+}
+
+#[cw_serde]
+pub struct ReferrerResponse {
+    pub stats: ReferrerStats,
+}
+
+#[cw_serde]
+pub struct ClaimableFeesResponse {
+    /// Quote amount accrued to the address and not yet claimed
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct FeePreviewResponse {
+    /// Taker fee the address would pay at the current block time
+    pub taker_pct: Uint128,
+    /// Maker fee the address would pay at the current block time
+    pub maker_pct: Uint128,
+    /// Override set for the address, which takes precedence over any tier
+    pub fee_override: Option<FeeOverride>,
+    /// Holder tier the address's balance qualifies for
+    pub holder_tier: Option<HolderTier>,
+    /// Quote volume the address traded over the rolling volume window
+    pub volume: Uint256,
+    /// Volume tier the address's rolling volume qualifies for
+    pub volume_tier: Option<VolumeTier>,
+    /// Tier whose discount applies, i.e. the one with the larger discount, with
+    /// ties going to the holder tier
+    pub applied_tier: Option<FeeTierKind>,
+}
+
+#[cw_serde]
+pub struct FeeOverrideEntry {
+    pub address: Addr,
+    pub fee_override: FeeOverride,
+}
+
+#[cw_serde]
+pub struct FeeOverridesResponse {
+    pub overrides: Vec<FeeOverrideEntry>,
+}
+
+#[cw_serde]
+pub struct DepthLevel {
+    /// Price once the level is filled
+    pub price: Decimal256,
+    /// Cumulative base amount bought from or sold into the curve
+    pub base_amount: Uint128,
+    /// Cumulative quote amount paid for asks or received for bids, after fees
+    pub quote_amount: Uint128,
+}
+
+#[cw_serde]
+pub struct DepthResponse {
+    pub price: Decimal256,
+    /// Levels above the current price, reached by buying
+    pub asks: Vec<DepthLevel>,
+    /// Levels below the current price, reached by selling
+    pub bids: Vec<DepthLevel>,
+}
+
+#[cw_serde]
+pub struct TrancheOverview {
+    pub price: Decimal256,
+    /// Raw base amount sold along the curve before the tranche starts
+    pub start_amount: Uint256,
+    /// Raw base amount sold along the curve once the tranche is sold out
+    pub end_amount: Uint256,
+    /// Raw base amount sold out of the tranche so far
+    pub sold_amount: Uint256,
+}
+
+#[cw_serde]
+pub struct TranchesResponse {
+    /// Tranche table of a stepped curve, or empty for other curve kinds
+    pub tranches: Vec<TrancheOverview>,
+}
+
+#[cw_serde]
+pub struct SimulationResponse {
+    /// Amount swapped in, including any fee charged in the input asset
+    pub gross_in_amount: Uint128,
+    /// Fee in the fee asset, taken out of whichever side of the swap is in
+    /// that asset
+    pub fee_amount: Uint128,
+    pub fee_asset: FeeAsset,
+    /// Amount that actually goes into the curve
+    pub net_in_amount: Uint128,
+    /// Amount received, net of any fee charged in the output asset
+    pub out_amount: Uint128,
+    /// Input left over by a partially filled buy
+    pub refund_amount: Uint128,
+    pub price_before: Decimal256,
+    pub price_after: Decimal256,
+    /// Fractional change in price caused by the swap
+    pub price_impact: Decimal256,
+}
+
+#[cw_serde]
+pub enum SimulationStep {
+    Buy { in_amount: Uint128 },
+    Sell { in_amount: Uint128 },
+}
+
+#[cw_serde]
+pub struct SimulateStepsResponse {
+    /// Result of each step, in the order given
+    pub steps: Vec<SimulationResponse>,
 }
 
 #[cw_serde]
 pub struct BuyMsg {
     pub initiator: Option<Addr>,
+    /// Account that referred the initiator, which earns a share of the fee
+    pub referrer: Option<Addr>,
     pub min_out_amount: Option<Uint128>,
+    /// Max allowed change in price caused by the swap, in basis points
+    pub max_price_impact_bps: Option<u32>,
 }
 
 #[cw_serde]
 pub struct SellMsg {
     pub initiator: Option<Addr>,
+    /// Account that referred the initiator, which earns a share of the fee
+    pub referrer: Option<Addr>,
     pub min_out_amount: Option<Uint128>,
+    /// Max allowed change in price caused by the swap, in basis points
+    pub max_price_impact_bps: Option<u32>,
+}
+
+#[cw_serde]
+pub struct BuyExactOutMsg {
+    pub initiator: Option<Addr>,
+    /// Account that referred the initiator, which earns a share of the fee
+    pub referrer: Option<Addr>,
+    /// Exact base amount to receive
+    pub out_amount: Uint128,
+    /// Max quote amount to spend, including fees. Unused quote is refunded.
+    pub max_in_amount: Option<Uint128>,
+    /// Max allowed change in price caused by the swap, in basis points
+    pub max_price_impact_bps: Option<u32>,
+}
+
+#[cw_serde]
+pub struct SellExactOutMsg {
+    pub initiator: Option<Addr>,
+    /// Account that referred the initiator, which earns a share of the fee
+    pub referrer: Option<Addr>,
+    /// Exact quote amount to receive, after fees
+    pub out_amount: Uint128,
+    /// Max base amount to spend. Unused base is refunded.
+    pub max_in_amount: Option<Uint128>,
+    /// Max allowed change in price caused by the swap, in basis points
+    pub max_price_impact_bps: Option<u32>,
 }
 
 #[cw_serde]
 pub enum Cw20ReceiveInnerMsg {
     Buy(BuyMsg),
     Sell(SellMsg),
+    BuyExactOut(BuyExactOutMsg),
+    SellExactOut(SellExactOutMsg),
 }
 
 #[cw_serde]
@@ -157,3 +462,10 @@ pub enum BalanceChangeEvent {
         amount: Uint128,
     },
 }
+
+#[cw_serde]
+pub struct SetFeeOverrideMsg {
+    pub address: Addr,
+    /// Override to set, or None to remove the address's override
+    pub fee_override: Option<FeeOverride>,
+}
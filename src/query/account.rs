@@ -1,6 +1,8 @@
 use cosmwasm_std::Addr;
 
-use crate::{error::ContractError, msg::AccountResponse, state::ACCOUNT_STATS};
+use crate::{
+    error::ContractError, models::account::calculate_rolling_volume, msg::AccountResponse, state::ACCOUNT_STATS,
+};
 
 use super::ReadonlyContext;
 
@@ -8,8 +10,10 @@ pub fn query_account(
     ctx: ReadonlyContext,
     address: Addr,
 ) -> Result<AccountResponse, ContractError> {
-    let ReadonlyContext { deps, .. } = ctx;
+    let ReadonlyContext { deps, env } = ctx;
+    let address = deps.api.addr_validate(address.as_str())?;
     Ok(AccountResponse {
-        stats: ACCOUNT_STATS.load(deps.storage, &deps.api.addr_validate(address.as_str())?)?,
+        stats: ACCOUNT_STATS.load(deps.storage, &address)?,
+        volume_30d: calculate_rolling_volume(deps.storage, &address, env.block.time)?,
     })
 }
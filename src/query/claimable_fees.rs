@@ -0,0 +1,17 @@
+use cosmwasm_std::Addr;
+
+use crate::{error::ContractError, msg::ClaimableFeesResponse, state::CLAIMABLE_FEES};
+
+use super::ReadonlyContext;
+
+pub fn query_claimable_fees(
+    ctx: ReadonlyContext,
+    address: Addr,
+) -> Result<ClaimableFeesResponse, ContractError> {
+    let ReadonlyContext { deps, .. } = ctx;
+    Ok(ClaimableFeesResponse {
+        amount: CLAIMABLE_FEES
+            .may_load(deps.storage, &deps.api.addr_validate(address.as_str())?)?
+            .unwrap_or_default(),
+    })
+}
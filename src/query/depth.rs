@@ -0,0 +1,203 @@
+use cosmwasm_std::{Uint128, Uint256};
+
+use crate::{
+    curve::CurveKind,
+    error::ContractError,
+    math::{gross_up_pct_u128, mul_pct_u128, mul_ratio_u256, sub_u128, to_u128},
+    models::fee::FeeAsset,
+    msg::{DepthLevel, DepthResponse},
+    state::{CURVE, FEE_ASSET_BUY, FEE_ASSET_SELL},
+    utils::{resolve_buy_fee_pct, resolve_sell_fee_pct},
+};
+
+use super::ReadonlyContext;
+
+pub const DEFAULT_DEPTH_LEVELS: u16 = 20;
+pub const MAX_DEPTH_LEVELS: u16 = 100;
+
+/// Builds a ladder of price levels on either side of the current price. Asks
+/// split the base left to sell into equal steps, reporting the quote it takes
+/// to buy through each one. Bids do the same for the base sold so far, reporting
+/// the quote received for selling it back. Amounts include fees, which come
+/// out of whichever side of the swap is in the fee asset.
+pub fn query_depth(
+    ctx: ReadonlyContext,
+    levels: Option<u16>,
+) -> Result<DepthResponse, ContractError> {
+    let ReadonlyContext { deps, env } = ctx;
+    let store = deps.storage;
+
+    let curve = CURVE.load(store)?;
+    let levels = levels.unwrap_or(DEFAULT_DEPTH_LEVELS).clamp(1, MAX_DEPTH_LEVELS);
+    let buy_fee_pct = resolve_buy_fee_pct(deps, env.block.time, None)?;
+    let sell_fee_pct = resolve_sell_fee_pct(deps, env.block.time, None)?;
+    let buy_fee_asset = FEE_ASSET_BUY.load(store)?;
+    let sell_fee_asset = FEE_ASSET_SELL.load(store)?;
+
+    let mut asks: Vec<DepthLevel> = Vec::with_capacity(levels as usize);
+    let mut bids: Vec<DepthLevel> = Vec::with_capacity(levels as usize);
+
+    // A constant product curve never sells its last base token, so the
+    // deepest ask stops one short of it
+    let base_askable = match curve.kind {
+        CurveKind::ConstantProduct {} => curve
+            .calculate_base_sellable()?
+            .min(curve.base_reserve.saturating_sub(Uint256::one())),
+        _ => curve.calculate_base_sellable()?,
+    };
+    let base_sold = curve.calculate_base_sold()?;
+
+    for i in 1..=levels {
+        let base_amount = step_amount(base_askable, i, levels)?;
+        if base_amount.is_zero() {
+            continue;
+        }
+        // Stop once the curve can no longer fill the level, e.g. when buying
+        // out a constant product curve's last tokens costs more than a
+        // Uint128 can hold.
+        let mut filled = curve.clone();
+        match filled.buy_exact_out(base_amount, None) {
+            Ok(quote_amount) => asks.push(match buy_fee_asset {
+                FeeAsset::Quote => DepthLevel {
+                    price: filled.calculate_quote_price()?,
+                    base_amount,
+                    quote_amount: gross_up_pct_u128(quote_amount, buy_fee_pct)?.0,
+                },
+                FeeAsset::Base => DepthLevel {
+                    price: filled.calculate_quote_price()?,
+                    base_amount: sub_u128(base_amount, mul_pct_u128(base_amount, buy_fee_pct)?)?,
+                    quote_amount,
+                },
+            }),
+            Err(_) => break,
+        }
+    }
+
+    for i in 1..=levels {
+        let base_amount = step_amount(base_sold, i, levels)?;
+        if base_amount.is_zero() {
+            continue;
+        }
+        let mut filled = curve.clone();
+        let sold_amount = match sell_fee_asset {
+            FeeAsset::Quote => base_amount,
+            FeeAsset::Base => sub_u128(base_amount, mul_pct_u128(base_amount, sell_fee_pct)?)?,
+        };
+        match filled.sell(sold_amount, None) {
+            Ok(quote_amount) => bids.push(DepthLevel {
+                price: filled.calculate_quote_price()?,
+                base_amount,
+                quote_amount: match sell_fee_asset {
+                    FeeAsset::Quote => sub_u128(quote_amount, mul_pct_u128(quote_amount, sell_fee_pct)?)?,
+                    FeeAsset::Base => quote_amount,
+                },
+            }),
+            Err(_) => break,
+        }
+    }
+
+    Ok(DepthResponse {
+        price: curve.calculate_quote_price()?,
+        asks,
+        bids,
+    })
+}
+
+fn step_amount(
+    total: Uint256,
+    step: u16,
+    steps: u16,
+) -> Result<Uint128, ContractError> {
+    to_u128(mul_ratio_u256(total, step, steps)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{coins, Uint256};
+
+    use crate::{
+        models::fee::FeeAsset,
+        msg::{BuyExactOutMsg, DepthResponse, ExecuteMsg, InstantiateMsg, QueryMsg},
+        testing::{
+            attr, buy, execute_as, instantiate_msg, overview, query_as, sell, setup_with, TestDeps, QUOTE_DENOM, TRADER,
+        },
+    };
+
+    /// Default constant product curve charging both sides' fees in the given
+    /// asset, with some base already bought
+    fn setup_depth(fee_asset: FeeAsset) -> TestDeps {
+        let mut deps = setup_with(InstantiateMsg {
+            lp_fee_pct: None,
+            taker_fee_asset: Some(fee_asset),
+            maker_fee_asset: Some(fee_asset),
+            ..instantiate_msg(30_000_000_000)
+        });
+        buy(&mut deps, 1_000_000_000);
+        deps
+    }
+
+    fn depth(
+        deps: &TestDeps,
+        levels: u16,
+    ) -> DepthResponse {
+        query_as(deps, QueryMsg::Depth { levels: Some(levels) })
+    }
+
+    #[test]
+    fn returns_every_level_requested() {
+        for fee_asset in [FeeAsset::Quote, FeeAsset::Base] {
+            let deps = setup_depth(fee_asset);
+            let depth = depth(&deps, 4);
+            assert_eq!(depth.asks.len(), 4);
+            assert_eq!(depth.bids.len(), 4);
+
+            // The deepest ask buys all but the curve's last base token
+            let base_reserve = overview(&deps).amm.base_reserve;
+            let last_ask = depth.asks.last().unwrap();
+            assert!(last_ask.price > depth.asks[2].price);
+            if fee_asset == FeeAsset::Quote {
+                assert_eq!(Uint256::from(last_ask.base_amount), base_reserve - Uint256::one());
+            }
+        }
+    }
+
+    #[test]
+    fn asks_match_buying_through_each_level() {
+        for ask in depth(&setup_depth(FeeAsset::Quote), 4).asks {
+            let mut deps = setup_depth(FeeAsset::Quote);
+            let msg = ExecuteMsg::BuyExactOut(BuyExactOutMsg {
+                initiator: None,
+                referrer: None,
+                out_amount: ask.base_amount,
+                max_in_amount: None,
+                max_price_impact_bps: None,
+            });
+            let resp = execute_as(&mut deps, TRADER, &coins(ask.quote_amount.u128(), QUOTE_DENOM), msg).unwrap();
+            assert_eq!(attr(&resp, "in_amount"), ask.quote_amount);
+            assert_eq!(attr(&resp, "out_amount"), ask.base_amount);
+            assert_eq!(overview(&deps).amm.quote_price, ask.price);
+        }
+
+        // With the fee in base, the level's quote is the least that buys at
+        // least its base after fees
+        for ask in depth(&setup_depth(FeeAsset::Base), 4).asks {
+            let resp = buy(&mut setup_depth(FeeAsset::Base), ask.quote_amount.u128());
+            assert!(attr(&resp, "out_amount") >= ask.base_amount);
+            let resp = buy(&mut setup_depth(FeeAsset::Base), ask.quote_amount.u128() - 1);
+            assert!(attr(&resp, "out_amount") < ask.base_amount);
+        }
+    }
+
+    #[test]
+    fn bids_match_selling_through_each_level() {
+        for fee_asset in [FeeAsset::Quote, FeeAsset::Base] {
+            for bid in depth(&setup_depth(fee_asset), 4).bids {
+                let mut deps = setup_depth(fee_asset);
+                let resp = sell(&mut deps, bid.base_amount.u128());
+                assert_eq!(attr(&resp, "in_amount"), bid.base_amount);
+                assert_eq!(attr(&resp, "out_amount"), bid.quote_amount);
+                assert_eq!(overview(&deps).amm.quote_price, bid.price);
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use cosmwasm_std::{Addr, Order};
+use cw_storage_plus::Bound;
+
+use crate::{
+    error::ContractError,
+    msg::{FeeOverrideEntry, FeeOverridesResponse},
+    state::FEE_OVERRIDES,
+};
+
+use super::ReadonlyContext;
+
+pub const DEFAULT_FEE_OVERRIDES_LIMIT: u16 = 50;
+pub const MAX_FEE_OVERRIDES_LIMIT: u16 = 200;
+
+/// Lists fee overrides in address order, starting after `start_after`
+pub fn query_fee_overrides(
+    ctx: ReadonlyContext,
+    start_after: Option<Addr>,
+    limit: Option<u16>,
+) -> Result<FeeOverridesResponse, ContractError> {
+    let ReadonlyContext { deps, .. } = ctx;
+    let limit = limit
+        .unwrap_or(DEFAULT_FEE_OVERRIDES_LIMIT)
+        .clamp(1, MAX_FEE_OVERRIDES_LIMIT);
+    let start_after = start_after
+        .map(|addr| deps.api.addr_validate(addr.as_str()))
+        .transpose()?;
+
+    let overrides = FEE_OVERRIDES
+        .range(
+            deps.storage,
+            start_after.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit as usize)
+        .map(|result| {
+            let (address, fee_override) = result?;
+            Ok(FeeOverrideEntry { address, fee_override })
+        })
+        .collect::<Result<Vec<_>, ContractError>>()?;
+
+    Ok(FeeOverridesResponse { overrides })
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{Addr, Uint128};
+
+    use crate::{
+        curve::CurveKind,
+        models::fee::FeeOverride,
+        msg::{ExecuteMsg, FeeOverridesResponse, QueryMsg, SetFeeOverrideMsg},
+        testing::{execute_as, query_as, setup, TestDeps, ADMIN},
+    };
+
+    fn fee_overrides(
+        deps: &TestDeps,
+        start_after: Option<&str>,
+        limit: Option<u16>,
+    ) -> Vec<String> {
+        let msg = QueryMsg::FeeOverrides {
+            start_after: start_after.map(Addr::unchecked),
+            limit,
+        };
+        query_as::<FeeOverridesResponse>(deps, msg)
+            .overrides
+            .into_iter()
+            .map(|entry| entry.address.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn pages_fee_overrides_in_address_order() {
+        let mut deps = setup(CurveKind::ConstantProduct {}, 30_000_000_000);
+        for (i, address) in ["carol", "alice", "dave", "bob"].into_iter().enumerate() {
+            let msg = ExecuteMsg::SetFeeOverride(SetFeeOverrideMsg {
+                address: Addr::unchecked(address),
+                fee_override: Some(FeeOverride::Custom {
+                    pct: Uint128::new(i as u128 * 1_000),
+                }),
+            });
+            execute_as(&mut deps, ADMIN, &[], msg).unwrap();
+        }
+
+        assert_eq!(fee_overrides(&deps, None, None), ["alice", "bob", "carol", "dave"]);
+        assert_eq!(fee_overrides(&deps, None, Some(2)), ["alice", "bob"]);
+        assert_eq!(fee_overrides(&deps, Some("bob"), Some(2)), ["carol", "dave"]);
+        assert_eq!(fee_overrides(&deps, Some("dave"), Some(2)), Vec::<String>::new());
+
+        // Starting after an address without an override picks up at the next one
+        assert_eq!(fee_overrides(&deps, Some("bobby"), None), ["carol", "dave"]);
+        // A zero limit still returns one entry
+        assert_eq!(fee_overrides(&deps, None, Some(0)), ["alice"]);
+    }
+}
@@ -0,0 +1,48 @@
+use cosmwasm_std::Addr;
+
+use crate::{
+    error::ContractError,
+    models::{account::calculate_rolling_volume, fee::FeeTierKind},
+    msg::FeePreviewResponse,
+    state::FEE_OVERRIDES,
+    utils::{resolve_buy_fee_pct, resolve_holder_tier, resolve_sell_fee_pct, resolve_volume_tier},
+};
+
+use super::ReadonlyContext;
+
+/// Fees the address would pay if it swapped now, and what set them
+pub fn query_fee_preview(
+    ctx: ReadonlyContext,
+    address: Addr,
+) -> Result<FeePreviewResponse, ContractError> {
+    let ReadonlyContext { deps, env } = ctx;
+    let address = deps.api.addr_validate(address.as_str())?;
+    let fee_override = FEE_OVERRIDES.may_load(deps.storage, &address)?;
+
+    let (holder_tier, volume_tier) = if fee_override.is_some() {
+        (None, None)
+    } else {
+        (
+            resolve_holder_tier(deps, Some(&address))?,
+            resolve_volume_tier(deps.storage, env.block.time, Some(&address))?,
+        )
+    };
+
+    // Mirrors the fee resolution, which takes the larger of the two discounts
+    let applied_tier = match (&holder_tier, &volume_tier) {
+        (Some(h), Some(v)) if v.discount_pct > h.discount_pct => Some(FeeTierKind::Volume),
+        (Some(_), _) => Some(FeeTierKind::Holder),
+        (None, Some(_)) => Some(FeeTierKind::Volume),
+        (None, None) => None,
+    };
+
+    Ok(FeePreviewResponse {
+        taker_pct: resolve_buy_fee_pct(deps, env.block.time, Some(&address))?,
+        maker_pct: resolve_sell_fee_pct(deps, env.block.time, Some(&address))?,
+        holder_tier,
+        volume_tier,
+        applied_tier,
+        volume: calculate_rolling_volume(deps.storage, &address, env.block.time)?,
+        fee_override,
+    })
+}
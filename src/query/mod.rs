@@ -1,6 +1,13 @@
 pub mod account;
+pub mod claimable_fees;
 pub mod config;
+pub mod depth;
+pub mod fee_overrides;
+pub mod fee_preview;
 pub mod overview;
+pub mod referrer;
+pub mod simulate;
+pub mod tranches;
 
 use cosmwasm_std::{Deps, Env};
 
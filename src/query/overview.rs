@@ -1,37 +1,87 @@
+use cosmwasm_std::Uint256;
+
 use crate::{
+    curve::CurveKind,
     error::ContractError,
-    math::sub_u128,
-    msg::{CurveAmmOverview, CurveFeeOverview, CurveStatsOverview, OverviewResponse},
+    math::from_ratio_dec256,
+    msg::{CurveAmmOverview, CurveFeeOverview, CurveStatsOverview, FeeRecipientOverview, OverviewResponse},
     state::{
-        BASE_TOKEN, CURVE, FEE_ADDR, FEE_PCT_BUY, FEE_PCT_SELL, MAKER_STATS, NET_MAKER_FEE, NET_TAKER_FEE,
-        QUOTE_RESERVE_VIRTUAL, QUOTE_TOKEN, TAKER_STATS,
+        BASE_TOKEN, CURVE, DYNAMIC_FEE, FEE_ADDR, FEE_ASSET_BUY, FEE_ASSET_SELL, FEE_PCT_BUY, FEE_PCT_SELL,
+        FEE_RECIPIENTS, FEE_RECIPIENT_BASE_TOTALS, FEE_RECIPIENT_TOTALS, HOLDER_TIERS, K_INITIAL, LAUNCH_FEE,
+        LP_FEE_PCT, LP_FEE_RETAINED, MAKER_STATS, NET_MAKER_FEE, NET_TAKER_FEE, QUOTE_TOKEN, REFERRAL_FEE_PCT,
+        TAKER_STATS, VOLUME_TIERS,
     },
+    utils::{resolve_buy_fee_pct, resolve_sell_fee_pct},
 };
 
 use super::ReadonlyContext;
 
 pub fn query_overview(ctx: ReadonlyContext) -> Result<OverviewResponse, ContractError> {
-    let ReadonlyContext { deps, .. } = ctx;
+    let ReadonlyContext { deps, env } = ctx;
     let store = deps.storage;
 
     let curve = CURVE.load(store)?;
-    let vl = QUOTE_RESERVE_VIRTUAL.load(store)?;
+    let k_initial = K_INITIAL.load(store)?;
 
     Ok(OverviewResponse {
         amm: CurveAmmOverview {
+            kind: curve.kind.clone(),
+            quote_price: curve.calculate_quote_price()?,
+            base_price: curve.calculate_base_price()?,
             base_token: BASE_TOKEN.load(store)?,
             base_reserve: curve.base_reserve,
+            base_reserve_real: curve.calculate_base_reserve_real()?,
+            base_reserve_virtual: curve.base_reserve_virtual,
+            base_supply: curve.base_supply,
+            base_sellable_supply: curve.sellable_supply,
+            base_sellable_remaining: curve.calculate_base_sellable()?,
             base_decimals: curve.base_decimals,
             quote_token: QUOTE_TOKEN.load(store)?,
-            quote_reserve_real: sub_u128(curve.quote_reserve, vl)?,
-            quote_reserve_virtual: vl,
+            quote_reserve_real: curve.calculate_quote_reserve_real()?,
+            quote_reserve_virtual: curve.quote_reserve_virtual,
             quote_decimals: curve.quote_decimals,
             constant_product: curve.k,
+            constant_product_initial: k_initial,
+            constant_product_growth: match curve.kind {
+                CurveKind::ConstantProduct {} if !k_initial.is_zero() => Some(from_ratio_dec256(curve.k, k_initial)?),
+                _ => None,
+            },
+            lp_fee_retained: LP_FEE_RETAINED.load(store)?,
+            start_price: curve.calculate_price_at(Uint256::zero())?,
+            end_price: match curve.kind {
+                CurveKind::ConstantProduct {} if curve.sellable_supply >= curve.base_supply => None,
+                _ => Some(curve.calculate_price_at(curve.sellable_supply)?),
+            },
+            virtual_liquidity: curve.calculate_virtual_liquidity()?,
         },
         fees: CurveFeeOverview {
             recipient: FEE_ADDR.load(store)?,
             taker_pct: FEE_PCT_BUY.load(store)?,
+            effective_taker_pct: resolve_buy_fee_pct(deps, env.block.time, None)?,
             maker_pct: FEE_PCT_SELL.load(store)?,
+            effective_maker_pct: resolve_sell_fee_pct(deps, env.block.time, None)?,
+            taker_fee_asset: FEE_ASSET_BUY.load(store)?,
+            maker_fee_asset: FEE_ASSET_SELL.load(store)?,
+            lp_pct: LP_FEE_PCT.load(store)?,
+            referral_pct: REFERRAL_FEE_PCT.load(store)?,
+            launch_fee: LAUNCH_FEE.may_load(store)?,
+            dynamic_fee: DYNAMIC_FEE.may_load(store)?,
+            holder_tiers: HOLDER_TIERS.may_load(store)?,
+            volume_tiers: VOLUME_TIERS.may_load(store)?,
+            recipients: FEE_RECIPIENTS
+                .load(store)?
+                .into_iter()
+                .map(|r| {
+                    Ok(FeeRecipientOverview {
+                        total_paid: FEE_RECIPIENT_TOTALS.may_load(store, &r.address)?.unwrap_or_default(),
+                        total_paid_base: FEE_RECIPIENT_BASE_TOTALS
+                            .may_load(store, &r.address)?
+                            .unwrap_or_default(),
+                        address: r.address,
+                        weight: r.weight,
+                    })
+                })
+                .collect::<Result<Vec<_>, ContractError>>()?,
         },
         stats: CurveStatsOverview {
             bids: TAKER_STATS.load(store)?,
@@ -41,3 +91,20 @@ pub fn query_overview(ctx: ReadonlyContext) -> Result<OverviewResponse, Contract
         },
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::Decimal256;
+
+    use crate::testing::{instantiate_msg, overview, setup_with};
+
+    #[test]
+    fn overview_with_default_curve() {
+        let deps = setup_with(instantiate_msg(30_000_000_000));
+        let overview = overview(&deps);
+        assert_eq!(overview.amm.base_sellable_supply, overview.amm.base_supply);
+        assert!(!overview.amm.start_price.is_zero());
+        assert_eq!(overview.amm.end_price, None);
+        assert_eq!(overview.amm.constant_product_growth, Some(Decimal256::one()));
+    }
+}
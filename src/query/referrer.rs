@@ -0,0 +1,17 @@
+use cosmwasm_std::Addr;
+
+use crate::{error::ContractError, msg::ReferrerResponse, state::REFERRER_STATS};
+
+use super::ReadonlyContext;
+
+pub fn query_referrer(
+    ctx: ReadonlyContext,
+    address: Addr,
+) -> Result<ReferrerResponse, ContractError> {
+    let ReadonlyContext { deps, .. } = ctx;
+    Ok(ReferrerResponse {
+        stats: REFERRER_STATS
+            .may_load(deps.storage, &deps.api.addr_validate(address.as_str())?)?
+            .unwrap_or_default(),
+    })
+}
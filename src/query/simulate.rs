@@ -0,0 +1,408 @@
+use cosmwasm_std::Uint128;
+
+use crate::{
+    error::ContractError,
+    math::sub_u128,
+    models::fee::FeeAsset,
+    msg::{SimulateStepsResponse, SimulationResponse, SimulationStep},
+    quote::{quote_buy, quote_buy_exact_out, quote_sell, quote_sell_exact_out, SwapQuote},
+    state::{CURVE, FEE_ASSET_BUY, FEE_ASSET_SELL, LP_FEE_PCT},
+    utils::{resolve_buy_fee_pct, resolve_sell_fee_pct},
+};
+
+use super::ReadonlyContext;
+
+pub const MAX_SIMULATION_STEPS: usize = 50;
+
+/// Simulates buying with `in_amount` quote, fee included
+pub fn query_simulate_buy(
+    ctx: ReadonlyContext,
+    in_amount: Uint128,
+) -> Result<SimulationResponse, ContractError> {
+    let ReadonlyContext { deps, env } = ctx;
+    let store = deps.storage;
+
+    let mut curve = CURVE.load(store)?;
+    let quote = quote_buy(
+        &mut curve,
+        resolve_buy_fee_pct(deps, env.block.time, None)?,
+        LP_FEE_PCT.load(store)?,
+        FEE_ASSET_BUY.load(store)?,
+        in_amount,
+        None,
+    )?;
+
+    build_buy_simulation_response(quote)
+}
+
+/// Simulates selling `in_amount` base
+pub fn query_simulate_sell(
+    ctx: ReadonlyContext,
+    in_amount: Uint128,
+) -> Result<SimulationResponse, ContractError> {
+    let ReadonlyContext { deps, env } = ctx;
+    let store = deps.storage;
+
+    let mut curve = CURVE.load(store)?;
+    let quote = quote_sell(
+        &mut curve,
+        resolve_sell_fee_pct(deps, env.block.time, None)?,
+        LP_FEE_PCT.load(store)?,
+        FEE_ASSET_SELL.load(store)?,
+        in_amount,
+        None,
+    )?;
+
+    build_sell_simulation_response(quote)
+}
+
+/// Simulates buying exactly `base_out`, returning the quote required
+pub fn query_reverse_simulate_buy(
+    ctx: ReadonlyContext,
+    base_out: Uint128,
+) -> Result<SimulationResponse, ContractError> {
+    let ReadonlyContext { deps, env } = ctx;
+    let store = deps.storage;
+
+    let mut curve = CURVE.load(store)?;
+    let quote = quote_buy_exact_out(
+        &mut curve,
+        resolve_buy_fee_pct(deps, env.block.time, None)?,
+        LP_FEE_PCT.load(store)?,
+        FEE_ASSET_BUY.load(store)?,
+        base_out,
+        None,
+    )?;
+
+    build_buy_simulation_response(quote)
+}
+
+/// Simulates selling for exactly `quote_out` after fees, returning the base
+/// required
+pub fn query_reverse_simulate_sell(
+    ctx: ReadonlyContext,
+    quote_out: Uint128,
+) -> Result<SimulationResponse, ContractError> {
+    let ReadonlyContext { deps, env } = ctx;
+    let store = deps.storage;
+
+    let mut curve = CURVE.load(store)?;
+    let quote = quote_sell_exact_out(
+        &mut curve,
+        resolve_sell_fee_pct(deps, env.block.time, None)?,
+        LP_FEE_PCT.load(store)?,
+        FEE_ASSET_SELL.load(store)?,
+        quote_out,
+        None,
+    )?;
+
+    build_sell_simulation_response(quote)
+}
+
+/// Simulates each step in order against the same in-memory copy of the curve,
+/// so that every step sees the price left by the ones before it.
+pub fn query_simulate_steps(
+    ctx: ReadonlyContext,
+    steps: Vec<SimulationStep>,
+) -> Result<SimulateStepsResponse, ContractError> {
+    let ReadonlyContext { deps, env } = ctx;
+    let store = deps.storage;
+
+    if steps.len() > MAX_SIMULATION_STEPS {
+        return Err(ContractError::ValidationError {
+            reason: format!("cannot simulate more than {} steps", MAX_SIMULATION_STEPS),
+        });
+    }
+
+    let mut curve = CURVE.load(store)?;
+    let buy_fee_pct = resolve_buy_fee_pct(deps, env.block.time, None)?;
+    let sell_fee_pct = resolve_sell_fee_pct(deps, env.block.time, None)?;
+    let lp_fee_pct = LP_FEE_PCT.load(store)?;
+    let buy_fee_asset = FEE_ASSET_BUY.load(store)?;
+    let sell_fee_asset = FEE_ASSET_SELL.load(store)?;
+
+    let mut results: Vec<SimulationResponse> = Vec::with_capacity(steps.len());
+
+    for step in steps.iter() {
+        results.push(match step {
+            SimulationStep::Buy { in_amount } => build_buy_simulation_response(quote_buy(
+                &mut curve,
+                buy_fee_pct,
+                lp_fee_pct,
+                buy_fee_asset,
+                *in_amount,
+                None,
+            )?)?,
+            SimulationStep::Sell { in_amount } => build_sell_simulation_response(quote_sell(
+                &mut curve,
+                sell_fee_pct,
+                lp_fee_pct,
+                sell_fee_asset,
+                *in_amount,
+                None,
+            )?)?,
+        });
+    }
+
+    Ok(SimulateStepsResponse { steps: results })
+}
+
+/// Builds a response for a buy, whose input is quote
+fn build_buy_simulation_response(quote: SwapQuote) -> Result<SimulationResponse, ContractError> {
+    build_simulation_response(quote, FeeAsset::Quote)
+}
+
+/// Builds a response for a sell, whose input is base
+fn build_sell_simulation_response(quote: SwapQuote) -> Result<SimulationResponse, ContractError> {
+    build_simulation_response(quote, FeeAsset::Base)
+}
+
+/// Builds a response with the fee taken out of the input if it's charged in
+/// the input asset, or out of the output otherwise
+fn build_simulation_response(
+    quote: SwapQuote,
+    in_asset: FeeAsset,
+) -> Result<SimulationResponse, ContractError> {
+    Ok(SimulationResponse {
+        gross_in_amount: quote.in_amount,
+        net_in_amount: if quote.fee_asset == in_asset {
+            sub_u128(quote.in_amount, quote.fee_amount)?
+        } else {
+            quote.in_amount
+        },
+        fee_amount: quote.fee_amount,
+        fee_asset: quote.fee_asset,
+        out_amount: quote.out_amount,
+        refund_amount: quote.refund_amount,
+        price_impact: quote.calculate_price_impact()?,
+        price_before: quote.price_before,
+        price_after: quote.price_after,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{coins, from_json, testing::mock_env, Response, Uint128, Uint256};
+
+    use super::MAX_SIMULATION_STEPS;
+    use crate::{
+        contract::query,
+        curve::CurveKind,
+        error::ContractError,
+        models::fee::FeeAsset,
+        msg::{
+            BuyExactOutMsg, Cw20ReceiveInnerMsg, ExecuteMsg, InstantiateMsg, QueryMsg, SellExactOutMsg,
+            SimulateStepsResponse, SimulationResponse, SimulationStep,
+        },
+        testing::{
+            attr, buy, claimable, execute_as, instantiate_msg, overview, query_as, receive, sell, setup, setup_with,
+            TestDeps, QUOTE_DENOM, TRADER,
+        },
+    };
+
+    /// Default curve whose whole fee goes to its recipient, with some base
+    /// already bought
+    fn setup_simulate() -> TestDeps {
+        let mut deps = setup_with(InstantiateMsg {
+            lp_fee_pct: None,
+            ..instantiate_msg(30_000_000_000)
+        });
+        buy(&mut deps, 1_000_000_000);
+        deps
+    }
+
+    /// Runs the swap, checking the simulation against what it did to the
+    /// curve and the fees
+    fn assert_swap_matches(
+        deps: &mut TestDeps,
+        sim: SimulationResponse,
+        swap: impl FnOnce(&mut TestDeps) -> Response,
+        reserve: fn(&TestDeps) -> Uint256,
+    ) {
+        let price_before = overview(deps).amm.quote_price;
+        let reserve_before = reserve(deps);
+        let fees_before = claimable(deps, "fees");
+
+        let resp = swap(deps);
+        let price_after = overview(deps).amm.quote_price;
+
+        assert_eq!(sim.gross_in_amount, attr(&resp, "in_amount"));
+        assert_eq!(sim.out_amount, attr(&resp, "out_amount"));
+        assert_eq!(sim.fee_amount, claimable(deps, "fees") - fees_before);
+        assert_eq!(Uint256::from(sim.net_in_amount), reserve(deps).abs_diff(reserve_before));
+        assert_eq!(sim.price_impact, price_before.abs_diff(price_after) / price_before);
+    }
+
+    #[test]
+    fn simulate_buy_matches_buying() {
+        let mut deps = setup_simulate();
+        let sim: SimulationResponse = query_as(
+            &deps,
+            QueryMsg::SimulateBuy {
+                in_amount: Uint128::new(2_000_000_000),
+            },
+        );
+        assert!(sim.net_in_amount < sim.gross_in_amount);
+        assert_swap_matches(
+            &mut deps,
+            sim,
+            |deps| buy(deps, 2_000_000_000),
+            |deps| overview(deps).amm.quote_reserve_real,
+        );
+    }
+
+    #[test]
+    fn simulate_sell_matches_selling() {
+        let mut deps = setup_simulate();
+        let sim: SimulationResponse = query_as(
+            &deps,
+            QueryMsg::SimulateSell {
+                in_amount: Uint128::new(10_000_000_000_000),
+            },
+        );
+        assert_eq!(sim.net_in_amount, sim.gross_in_amount);
+        assert_swap_matches(
+            &mut deps,
+            sim,
+            |deps| sell(deps, 10_000_000_000_000),
+            |deps| overview(deps).amm.base_reserve,
+        );
+    }
+
+    /// Default curve charging both sides' fees in the given asset
+    fn setup_reverse(fee_asset: FeeAsset) -> TestDeps {
+        let mut deps = setup_with(InstantiateMsg {
+            lp_fee_pct: None,
+            taker_fee_asset: Some(fee_asset),
+            maker_fee_asset: Some(fee_asset),
+            ..instantiate_msg(30_000_000_000)
+        });
+        buy(&mut deps, 1_000_000_000);
+        deps
+    }
+
+    #[test]
+    fn reverse_simulate_buy_buys_exact_out() {
+        for fee_asset in [FeeAsset::Quote, FeeAsset::Base] {
+            let mut deps = setup_reverse(fee_asset);
+            let base_out = Uint128::new(10_000_000_000_000);
+            let sim: SimulationResponse = query_as(&deps, QueryMsg::ReverseSimulateBuy { base_out });
+            assert_eq!(sim.out_amount, base_out);
+
+            let msg = ExecuteMsg::BuyExactOut(BuyExactOutMsg {
+                initiator: None,
+                referrer: None,
+                out_amount: base_out,
+                max_in_amount: None,
+                max_price_impact_bps: None,
+            });
+            let funds = coins(sim.gross_in_amount.u128(), QUOTE_DENOM);
+            let resp = execute_as(&mut deps, TRADER, &funds, msg).unwrap();
+            assert_eq!(attr(&resp, "in_amount"), sim.gross_in_amount);
+            assert_eq!(attr(&resp, "out_amount"), base_out);
+            assert_eq!(attr(&resp, "refund_amount"), Uint128::zero());
+        }
+    }
+
+    #[test]
+    fn reverse_simulate_sell_sells_exact_out() {
+        for fee_asset in [FeeAsset::Quote, FeeAsset::Base] {
+            let mut deps = setup_reverse(fee_asset);
+            let quote_out = Uint128::new(100_000_000);
+            let sim: SimulationResponse = query_as(&deps, QueryMsg::ReverseSimulateSell { quote_out });
+            assert_eq!(sim.out_amount, quote_out);
+
+            let msg = Cw20ReceiveInnerMsg::SellExactOut(SellExactOutMsg {
+                initiator: None,
+                referrer: None,
+                out_amount: quote_out,
+                max_in_amount: None,
+                max_price_impact_bps: None,
+            });
+            let resp = receive(&mut deps, TRADER, sim.gross_in_amount.u128(), msg).unwrap();
+            assert_eq!(attr(&resp, "in_amount"), sim.gross_in_amount);
+            assert_eq!(attr(&resp, "out_amount"), quote_out);
+            assert_eq!(attr(&resp, "refund_amount"), Uint128::zero());
+        }
+    }
+
+    fn simulate_steps(
+        deps: &TestDeps,
+        steps: Vec<SimulationStep>,
+    ) -> Result<SimulateStepsResponse, ContractError> {
+        let msg = QueryMsg::SimulateSteps { steps };
+        query(deps.as_ref(), mock_env(), msg).map(|resp| from_json(resp).unwrap())
+    }
+
+    #[test]
+    fn simulate_steps_chains_each_step() {
+        let deps = setup(CurveKind::ConstantProduct {}, 30_000_000_000);
+        let in_amount = Uint128::new(1_000_000_000);
+        let sims = simulate_steps(&deps, vec![SimulationStep::Buy { in_amount }; 3])
+            .unwrap()
+            .steps;
+
+        // Each buy starts at the price left by the one before and gets less
+        for (prev, next) in sims.iter().zip(sims.iter().skip(1)) {
+            assert_eq!(next.price_before, prev.price_after);
+            assert!(next.price_after > prev.price_after);
+            assert!(next.out_amount < prev.out_amount);
+        }
+    }
+
+    #[test]
+    fn simulate_steps_matches_swapping_in_turn() {
+        let mut deps = setup(CurveKind::ConstantProduct {}, 30_000_000_000);
+        let steps = vec![
+            SimulationStep::Buy {
+                in_amount: Uint128::new(2_000_000_000),
+            },
+            SimulationStep::Sell {
+                in_amount: Uint128::new(10_000_000_000_000),
+            },
+            SimulationStep::Buy {
+                in_amount: Uint128::new(500_000_000),
+            },
+            SimulationStep::Sell {
+                in_amount: Uint128::new(20_000_000_000_000),
+            },
+        ];
+        let sims = simulate_steps(&deps, steps.clone()).unwrap().steps;
+        assert_eq!(sims.len(), steps.len());
+
+        for (step, sim) in steps.into_iter().zip(sims) {
+            let (single, resp) = match step {
+                SimulationStep::Buy { in_amount } => (
+                    query_as::<SimulationResponse>(&deps, QueryMsg::SimulateBuy { in_amount }),
+                    buy(&mut deps, in_amount.u128()),
+                ),
+                SimulationStep::Sell { in_amount } => (
+                    query_as::<SimulationResponse>(&deps, QueryMsg::SimulateSell { in_amount }),
+                    sell(&mut deps, in_amount.u128()),
+                ),
+            };
+            assert_eq!(sim, single);
+            assert_eq!(sim.gross_in_amount, attr(&resp, "in_amount"));
+            assert_eq!(sim.out_amount, attr(&resp, "out_amount"));
+            assert_eq!(sim.price_after, overview(&deps).amm.quote_price);
+        }
+    }
+
+    #[test]
+    fn simulate_steps_rejects_too_many_steps() {
+        let deps = setup(CurveKind::ConstantProduct {}, 30_000_000_000);
+        let step = SimulationStep::Buy {
+            in_amount: Uint128::new(1_000),
+        };
+        simulate_steps(&deps, vec![step.clone(); MAX_SIMULATION_STEPS]).unwrap();
+        match simulate_steps(&deps, vec![step; MAX_SIMULATION_STEPS + 1]) {
+            Err(ContractError::ValidationError { reason }) => {
+                assert_eq!(
+                    reason,
+                    format!("cannot simulate more than {} steps", MAX_SIMULATION_STEPS)
+                );
+            },
+            result => panic!("expected ValidationError, got {:?}", result),
+        }
+    }
+}
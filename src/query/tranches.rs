@@ -0,0 +1,36 @@
+use cosmwasm_std::Uint256;
+
+use crate::{
+    curve::CurveKind,
+    error::ContractError,
+    math::sub_u256,
+    msg::{TrancheOverview, TranchesResponse},
+    state::CURVE,
+};
+
+use super::ReadonlyContext;
+
+pub fn query_tranches(ctx: ReadonlyContext) -> Result<TranchesResponse, ContractError> {
+    let ReadonlyContext { deps, .. } = ctx;
+    let curve = CURVE.load(deps.storage)?;
+
+    let mut tranches: Vec<TrancheOverview> = vec![];
+
+    if let CurveKind::Stepped { tranches: ref table } = curve.kind {
+        let sold = curve.calculate_base_sold()?;
+        for (start, end, price) in curve.calculate_tranche_ranges(table)? {
+            tranches.push(TrancheOverview {
+                price,
+                start_amount: start,
+                end_amount: end,
+                sold_amount: if sold <= start {
+                    Uint256::zero()
+                } else {
+                    sub_u256(sold.min(end), start)?
+                },
+            });
+        }
+    }
+
+    Ok(TranchesResponse { tranches })
+}
@@ -0,0 +1,642 @@
+//! Pure swap quoting, shared by the contract's swap handlers and off-chain
+//! clients. Build with the `library` feature to use it without the contract's
+//! entry points. A `Quoter` built from an `OverviewResponse` reproduces the
+//! contract's amounts, fees and rounding exactly.
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Decimal256, Uint128};
+
+use crate::{
+    curve::Curve,
+    error::ContractError,
+    math::{add_u256, div_dec256, gross_up_pct_u128, mul_pct_u128, mul_ratio_u128, sub_u128},
+    models::fee::FeeAsset,
+    msg::{CurveFeeOverview, FeePreviewResponse, OverviewResponse},
+};
+
+/// Amounts moved by a swap, from the initiator's point of view
+#[cw_serde]
+pub struct SwapQuote {
+    /// Amount spent by the initiator, including fees for buys
+    pub in_amount: Uint128,
+    /// Amount received by the initiator, net of fees for sells
+    pub out_amount: Uint128,
+    /// Total fee charged, in the fee asset
+    pub fee_amount: Uint128,
+    /// Fee pct the fee was charged at, scaled by 1e6
+    pub fee_pct: Uint128,
+    pub fee_asset: FeeAsset,
+    /// Portion of the quote fee retained in the curve's reserves
+    pub lp_fee_amount: Uint128,
+    /// Unspent portion of the amount sent in, returned to the initiator
+    pub refund_amount: Uint128,
+    pub price_before: Decimal256,
+    pub price_after: Decimal256,
+}
+
+impl SwapQuote {
+    /// Fractional change in price caused by the swap
+    pub fn calculate_price_impact(&self) -> Result<Decimal256, ContractError> {
+        div_dec256(self.price_before.abs_diff(self.price_after), self.price_before)
+    }
+}
+
+/// Quotes swaps against a local copy of the curve. Each quote is applied to
+/// the copy, so consecutive quotes follow on from one another.
+/// Swaps are charged the effective fees as of the overview's block, so a
+/// decaying launch fee or a dynamic fee calls for a fresh overview. These are
+/// the untiered fees, which ignore the initiator's fee override, holder tier
+/// and volume tier; quote for a specific initiator via `with_fee_preview`.
+#[cw_serde]
+pub struct Quoter {
+    pub curve: Curve,
+    pub fees: CurveFeeOverview,
+}
+
+impl Quoter {
+    pub fn new(overview: &OverviewResponse) -> Result<Self, ContractError> {
+        let amm = &overview.amm;
+        Ok(Self {
+            curve: Curve {
+                kind: amm.kind.clone(),
+                k: amm.constant_product,
+                base_supply: amm.base_supply,
+                sellable_supply: amm.base_sellable_supply,
+                base_reserve: amm.base_reserve,
+                base_reserve_virtual: amm.base_reserve_virtual,
+                base_decimals: amm.base_decimals,
+                quote_reserve: add_u256(amm.quote_reserve_real, amm.quote_reserve_virtual)?,
+                quote_reserve_virtual: amm.quote_reserve_virtual,
+                quote_decimals: amm.quote_decimals,
+            },
+            fees: overview.fees.clone(),
+        })
+    }
+
+    /// Charges swaps the fees that the `FeePreview` query reports for an
+    /// initiator, in place of the untiered fees.
+    pub fn with_fee_preview(
+        mut self,
+        preview: &FeePreviewResponse,
+    ) -> Self {
+        self.fees.effective_taker_pct = preview.taker_pct;
+        self.fees.effective_maker_pct = preview.maker_pct;
+        self
+    }
+
+    pub fn buy(
+        &mut self,
+        in_amount: Uint128,
+        min_out_amount: Option<Uint128>,
+    ) -> Result<SwapQuote, ContractError> {
+        quote_buy(
+            &mut self.curve,
+            self.fees.effective_taker_pct,
+            self.fees.lp_pct,
+            self.fees.taker_fee_asset,
+            in_amount,
+            min_out_amount,
+        )
+    }
+
+    pub fn buy_exact_out(
+        &mut self,
+        out_amount: Uint128,
+        max_in_amount: Option<Uint128>,
+    ) -> Result<SwapQuote, ContractError> {
+        quote_buy_exact_out(
+            &mut self.curve,
+            self.fees.effective_taker_pct,
+            self.fees.lp_pct,
+            self.fees.taker_fee_asset,
+            out_amount,
+            max_in_amount,
+        )
+    }
+
+    pub fn sell(
+        &mut self,
+        in_amount: Uint128,
+        min_out_amount: Option<Uint128>,
+    ) -> Result<SwapQuote, ContractError> {
+        quote_sell(
+            &mut self.curve,
+            self.fees.effective_maker_pct,
+            self.fees.lp_pct,
+            self.fees.maker_fee_asset,
+            in_amount,
+            min_out_amount,
+        )
+    }
+
+    pub fn sell_exact_out(
+        &mut self,
+        out_amount: Uint128,
+        max_in_amount: Option<Uint128>,
+    ) -> Result<SwapQuote, ContractError> {
+        quote_sell_exact_out(
+            &mut self.curve,
+            self.fees.effective_maker_pct,
+            self.fees.lp_pct,
+            self.fees.maker_fee_asset,
+            out_amount,
+            max_in_amount,
+        )
+    }
+}
+
+/// Buys base with `in_amount` quote. A quote fee is included in `in_amount`,
+/// whereas a base fee comes out of the base bought. If the buy is only
+/// partially filled, the fee is charged on the filled part alone and the rest
+/// is refunded.
+pub fn quote_buy(
+    curve: &mut Curve,
+    fee_pct: Uint128,
+    lp_pct: Uint128,
+    fee_asset: FeeAsset,
+    in_amount: Uint128,
+    min_out_amount: Option<Uint128>,
+) -> Result<SwapQuote, ContractError> {
+    if fee_asset == FeeAsset::Base {
+        let price_before = curve.calculate_quote_price()?;
+        let (out_amount_pre_fee, unused_in_amount) = curve.buy(in_amount, None)?;
+
+        // Subtract fee from amount received
+        let fee_amount = mul_pct_u128(out_amount_pre_fee, fee_pct)?;
+        let out_amount = sub_u128(out_amount_pre_fee, fee_amount)?;
+        ensure_min_out_amount(out_amount, min_out_amount)?;
+
+        return Ok(SwapQuote {
+            in_amount: sub_u128(in_amount, unused_in_amount)?,
+            out_amount,
+            fee_amount,
+            fee_pct,
+            fee_asset,
+            lp_fee_amount: Uint128::zero(),
+            refund_amount: unused_in_amount,
+            price_before,
+            price_after: curve.calculate_quote_price()?,
+        });
+    }
+
+    let in_amount_pre_fee = in_amount;
+
+    // Subtract fee from amount swapped in
+    let fee_amount = mul_pct_u128(in_amount_pre_fee, fee_pct)?;
+    let in_amount = sub_u128(in_amount_pre_fee, fee_amount)?;
+
+    let price_before = curve.calculate_quote_price()?;
+    let (out_amount, unused_in_amount) = curve.buy(in_amount, min_out_amount)?;
+
+    let (in_amount_pre_fee, fee_amount, refund_amount) = if unused_in_amount.is_zero() {
+        (in_amount_pre_fee, fee_amount, Uint128::zero())
+    } else {
+        let filled_in_amount = sub_u128(in_amount, unused_in_amount)?;
+        let (filled_in_amount_pre_fee, _) = gross_up_pct_u128(filled_in_amount, fee_pct)?;
+        let filled_in_amount_pre_fee = filled_in_amount_pre_fee.min(in_amount_pre_fee);
+        (
+            filled_in_amount_pre_fee,
+            sub_u128(filled_in_amount_pre_fee, filled_in_amount)?,
+            sub_u128(in_amount_pre_fee, filled_in_amount_pre_fee)?,
+        )
+    };
+
+    let lp_fee_amount = retain_lp_fee(curve, lp_pct, fee_amount)?;
+
+    Ok(SwapQuote {
+        in_amount: in_amount_pre_fee,
+        out_amount,
+        fee_amount,
+        fee_pct,
+        fee_asset,
+        lp_fee_amount,
+        refund_amount,
+        price_before,
+        price_after: curve.calculate_quote_price()?,
+    })
+}
+
+/// Buys exactly `out_amount` base, after any base fee. `max_in_amount`
+/// includes any quote fee.
+pub fn quote_buy_exact_out(
+    curve: &mut Curve,
+    fee_pct: Uint128,
+    lp_pct: Uint128,
+    fee_asset: FeeAsset,
+    out_amount: Uint128,
+    max_in_amount: Option<Uint128>,
+) -> Result<SwapQuote, ContractError> {
+    if fee_asset == FeeAsset::Base {
+        // Gross the amount to receive up by the fee, giving the amount that
+        // the curve itself must pay out.
+        let (out_amount_pre_fee, fee_amount) = gross_up_pct_u128(out_amount, fee_pct)?;
+
+        let price_before = curve.calculate_quote_price()?;
+        let in_amount = curve.buy_exact_out(out_amount_pre_fee, max_in_amount)?;
+
+        return Ok(SwapQuote {
+            in_amount,
+            out_amount,
+            fee_amount,
+            fee_pct,
+            fee_asset,
+            lp_fee_amount: Uint128::zero(),
+            refund_amount: Uint128::zero(),
+            price_before,
+            price_after: curve.calculate_quote_price()?,
+        });
+    }
+
+    // The max in amount includes fees, whereas the curve only sees the amount
+    // net of fees, so translate one into the other.
+    let max_in_amount = max_in_amount.map(|n| net_of_pct(n, fee_pct)).transpose()?;
+
+    let price_before = curve.calculate_quote_price()?;
+    let in_amount = curve.buy_exact_out(out_amount, max_in_amount)?;
+
+    // Gross the amount swapped in up by the fee
+    let (in_amount_pre_fee, fee_amount) = gross_up_pct_u128(in_amount, fee_pct)?;
+
+    let lp_fee_amount = retain_lp_fee(curve, lp_pct, fee_amount)?;
+
+    Ok(SwapQuote {
+        in_amount: in_amount_pre_fee,
+        out_amount,
+        fee_amount,
+        fee_pct,
+        fee_asset,
+        lp_fee_amount,
+        refund_amount: Uint128::zero(),
+        price_before,
+        price_after: curve.calculate_quote_price()?,
+    })
+}
+
+/// Sells `in_amount` base for quote. A quote fee comes out of the quote
+/// received, whereas a base fee is included in `in_amount`.
+pub fn quote_sell(
+    curve: &mut Curve,
+    fee_pct: Uint128,
+    lp_pct: Uint128,
+    fee_asset: FeeAsset,
+    in_amount: Uint128,
+    min_out_amount: Option<Uint128>,
+) -> Result<SwapQuote, ContractError> {
+    if fee_asset == FeeAsset::Base {
+        // Subtract fee from amount swapped in
+        let fee_amount = mul_pct_u128(in_amount, fee_pct)?;
+
+        let price_before = curve.calculate_quote_price()?;
+        let out_amount = curve.sell(sub_u128(in_amount, fee_amount)?, min_out_amount)?;
+
+        return Ok(SwapQuote {
+            in_amount,
+            out_amount,
+            fee_amount,
+            fee_pct,
+            fee_asset,
+            lp_fee_amount: Uint128::zero(),
+            refund_amount: Uint128::zero(),
+            price_before,
+            price_after: curve.calculate_quote_price()?,
+        });
+    }
+
+    let price_before = curve.calculate_quote_price()?;
+    let out_amount_pre_fee = curve.sell(in_amount, min_out_amount)?;
+
+    // Subtract fee from amount received
+    let fee_amount = mul_pct_u128(out_amount_pre_fee, fee_pct)?;
+    let out_amount = sub_u128(out_amount_pre_fee, fee_amount)?;
+
+    let lp_fee_amount = retain_lp_fee(curve, lp_pct, fee_amount)?;
+
+    Ok(SwapQuote {
+        in_amount,
+        out_amount,
+        fee_amount,
+        fee_pct,
+        fee_asset,
+        lp_fee_amount,
+        refund_amount: Uint128::zero(),
+        price_before,
+        price_after: curve.calculate_quote_price()?,
+    })
+}
+
+/// Sells base for exactly `out_amount` quote, after any quote fee.
+/// `max_in_amount` includes any base fee.
+pub fn quote_sell_exact_out(
+    curve: &mut Curve,
+    fee_pct: Uint128,
+    lp_pct: Uint128,
+    fee_asset: FeeAsset,
+    out_amount: Uint128,
+    max_in_amount: Option<Uint128>,
+) -> Result<SwapQuote, ContractError> {
+    if fee_asset == FeeAsset::Base {
+        // The max in amount includes fees, whereas the curve only sees the
+        // amount net of fees, so translate one into the other.
+        let max_in_amount = max_in_amount.map(|n| net_of_pct(n, fee_pct)).transpose()?;
+
+        let price_before = curve.calculate_quote_price()?;
+        let in_amount = curve.sell_exact_out(out_amount, max_in_amount)?;
+
+        // Gross the amount swapped in up by the fee
+        let (in_amount_pre_fee, fee_amount) = gross_up_pct_u128(in_amount, fee_pct)?;
+
+        return Ok(SwapQuote {
+            in_amount: in_amount_pre_fee,
+            out_amount,
+            fee_amount,
+            fee_pct,
+            fee_asset,
+            lp_fee_amount: Uint128::zero(),
+            refund_amount: Uint128::zero(),
+            price_before,
+            price_after: curve.calculate_quote_price()?,
+        });
+    }
+
+    // Gross the amount to receive up by the fee, giving the amount that the
+    // curve itself must pay out.
+    let (out_amount_pre_fee, fee_amount) = gross_up_pct_u128(out_amount, fee_pct)?;
+
+    let price_before = curve.calculate_quote_price()?;
+    let in_amount = curve.sell_exact_out(out_amount_pre_fee, max_in_amount)?;
+
+    let lp_fee_amount = retain_lp_fee(curve, lp_pct, fee_amount)?;
+
+    Ok(SwapQuote {
+        in_amount,
+        out_amount,
+        fee_amount,
+        fee_pct,
+        fee_asset,
+        lp_fee_amount,
+        refund_amount: Uint128::zero(),
+        price_before,
+        price_after: curve.calculate_quote_price()?,
+    })
+}
+
+/// Portion of an amount left after taking out the given pct
+fn net_of_pct(
+    amount: Uint128,
+    pct: Uint128,
+) -> Result<Uint128, ContractError> {
+    mul_ratio_u128(amount, sub_u128(1_000_000u128, pct)?, 1_000_000u128)
+}
+
+fn ensure_min_out_amount(
+    out_amount: Uint128,
+    min_out_amount: Option<Uint128>,
+) -> Result<(), ContractError> {
+    if let Some(min_out_amount) = min_out_amount {
+        if out_amount < min_out_amount {
+            return Err(ContractError::TooMuchSlippage {});
+        }
+    }
+    Ok(())
+}
+
+/// Deposits the LP portion of a fee back into the curve, deepening it, and
+/// returns the amount deposited.
+fn retain_lp_fee(
+    curve: &mut Curve,
+    lp_pct: Uint128,
+    fee_amount: Uint128,
+) -> Result<Uint128, ContractError> {
+    let lp_fee_amount = mul_pct_u128(fee_amount, lp_pct)?;
+    if !lp_fee_amount.is_zero() {
+        curve.deposit_quote(lp_fee_amount)?;
+    }
+    Ok(lp_fee_amount)
+}
+
+#[cfg(all(test, not(feature = "library")))]
+mod tests {
+    use cosmwasm_std::{coins, to_json_binary, Addr, Uint256};
+
+    use super::*;
+    use crate::{
+        curve::{CurveKind, Tranche},
+        models::fee::FeeOverride,
+        msg::{BuyExactOutMsg, Cw20ReceiveInnerMsg, ExecuteMsg, InstantiateMsg, SellExactOutMsg, SetFeeOverrideMsg},
+        testing::{
+            attr, buy, execute_as, fee_preview, instantiate_msg, overview, receive, sell, setup, setup_with, TestDeps,
+            ADMIN, QUOTE_DENOM, TRADER,
+        },
+    };
+
+    enum Swap {
+        Buy(u128),
+        BuyExactOut(u128, u128),
+        Sell(u128),
+        SellExactOut(u128),
+    }
+
+    fn net_fee(
+        overview: &OverviewResponse,
+        asset: FeeAsset,
+    ) -> Uint256 {
+        let stats = &overview.stats;
+        match asset {
+            FeeAsset::Quote => stats.net_taker_fee.quote + stats.net_maker_fee.quote,
+            FeeAsset::Base => stats.net_taker_fee.base + stats.net_maker_fee.base,
+        }
+    }
+
+    /// Runs each swap through the contract and the quoter side by side,
+    /// checking that both produce the same amounts and the same curve.
+    fn assert_matches_contract(
+        deps: &mut TestDeps,
+        swaps: Vec<Swap>,
+    ) {
+        for swap in swaps {
+            let before = overview(deps);
+            let mut quoter = Quoter::new(&before)
+                .unwrap()
+                .with_fee_preview(&fee_preview(deps, TRADER));
+            let (resp, quote, sent) = match swap {
+                Swap::Buy(amount) => {
+                    let resp = buy(deps, amount);
+                    (resp, quoter.buy(Uint128::new(amount), None).unwrap(), None)
+                },
+                Swap::BuyExactOut(out_amount, sent) => {
+                    let msg = ExecuteMsg::BuyExactOut(BuyExactOutMsg {
+                        initiator: None,
+                        referrer: None,
+                        out_amount: Uint128::new(out_amount),
+                        max_in_amount: None,
+                        max_price_impact_bps: None,
+                    });
+                    let resp = execute_as(deps, TRADER, &coins(sent, QUOTE_DENOM), msg).unwrap();
+                    let quote = quoter.buy_exact_out(Uint128::new(out_amount), None).unwrap();
+                    (resp, quote, Some(sent))
+                },
+                Swap::Sell(amount) => {
+                    let resp = sell(deps, amount);
+                    (resp, quoter.sell(Uint128::new(amount), None).unwrap(), None)
+                },
+                Swap::SellExactOut(out_amount) => {
+                    let quote = quoter.clone().sell_exact_out(Uint128::new(out_amount), None).unwrap();
+                    let msg = Cw20ReceiveInnerMsg::SellExactOut(SellExactOutMsg {
+                        initiator: None,
+                        referrer: None,
+                        out_amount: Uint128::new(out_amount),
+                        max_in_amount: None,
+                        max_price_impact_bps: None,
+                    });
+                    let sent = quote.in_amount.u128() + 1_000;
+                    let resp = receive(deps, TRADER, sent, msg).unwrap();
+                    (
+                        resp,
+                        quoter.sell_exact_out(Uint128::new(out_amount), None).unwrap(),
+                        Some(sent),
+                    )
+                },
+            };
+
+            assert_eq!(attr(&resp, "in_amount"), quote.in_amount);
+            assert_eq!(attr(&resp, "out_amount"), quote.out_amount);
+            let refund_amount = match sent {
+                Some(sent) => Uint128::new(sent) - quote.in_amount,
+                None => quote.refund_amount,
+            };
+            assert_eq!(attr(&resp, "refund_amount"), refund_amount);
+
+            let after = overview(deps);
+            assert_eq!(
+                to_json_binary(&Quoter::new(&after).unwrap().curve).unwrap(),
+                to_json_binary(&quoter.curve).unwrap()
+            );
+            assert_eq!(
+                after.amm.lp_fee_retained - before.amm.lp_fee_retained,
+                Uint256::from(quote.lp_fee_amount)
+            );
+            let paid_fee = quote.fee_amount - quote.lp_fee_amount;
+            assert_eq!(
+                net_fee(&after, quote.fee_asset) - net_fee(&before, quote.fee_asset),
+                Uint256::from(paid_fee)
+            );
+
+            // Base fees are sent straight to the fee recipient, while quote
+            // fees accrue until claimed
+            let recipient = &after.fees.recipients[0];
+            let paid_base = recipient.total_paid_base - before.fees.recipients[0].total_paid_base;
+            let fee_transfer = after.amm.base_token.transfer(&recipient.address, paid_fee).unwrap();
+            match quote.fee_asset {
+                FeeAsset::Base => {
+                    assert_eq!(paid_base, Uint256::from(paid_fee));
+                    assert_eq!(resp.messages.contains(&fee_transfer), !paid_fee.is_zero());
+                },
+                FeeAsset::Quote => {
+                    assert!(paid_base.is_zero());
+                    assert!(!resp.messages.contains(&fee_transfer));
+                },
+            }
+        }
+    }
+
+    fn swaps() -> Vec<Swap> {
+        vec![
+            Swap::Buy(1_234_567_891),
+            Swap::BuyExactOut(7_777_777_777, 1_000_000_000_000),
+            Swap::Sell(3_333_333_333),
+            Swap::Buy(98_765_432_109),
+            Swap::SellExactOut(123_456_789),
+            Swap::Sell(1_000_000_007),
+            Swap::BuyExactOut(1, 1_000_000),
+            Swap::SellExactOut(1),
+        ]
+    }
+
+    #[test]
+    fn matches_constant_product_contract() {
+        let mut deps = setup(CurveKind::ConstantProduct {}, 30_000_000_000);
+        assert_matches_contract(&mut deps, swaps());
+        assert!(overview(&deps).amm.constant_product_growth.unwrap() > Decimal256::one());
+    }
+
+    #[test]
+    fn matches_linear_contract() {
+        let mut deps = setup(
+            CurveKind::Linear {
+                start_price: Decimal256::from_ratio(3u128, 100_000u128),
+                end_price: Decimal256::from_ratio(3u128, 1_000u128),
+            },
+            0,
+        );
+        assert_matches_contract(&mut deps, swaps());
+        assert_eq!(overview(&deps).amm.constant_product_growth, None);
+    }
+
+    #[test]
+    fn matches_stepped_contract() {
+        let mut deps = setup(
+            CurveKind::Stepped {
+                tranches: vec![
+                    Tranche {
+                        supply_pct: Uint128::new(1_000),
+                        price: Decimal256::from_ratio(3u128, 100_000u128),
+                    },
+                    Tranche {
+                        supply_pct: Uint128::new(999_000),
+                        price: Decimal256::from_ratio(7u128, 100_000u128),
+                    },
+                ],
+            },
+            0,
+        );
+        assert_matches_contract(&mut deps, swaps());
+    }
+
+    #[test]
+    fn matches_default_contract() {
+        let mut deps = setup_with(instantiate_msg(30_000_000_000));
+        assert_matches_contract(&mut deps, swaps());
+    }
+
+    #[test]
+    fn fee_override_matches_contract() {
+        let mut deps = setup(CurveKind::ConstantProduct {}, 30_000_000_000);
+        let msg = ExecuteMsg::SetFeeOverride(SetFeeOverrideMsg {
+            address: Addr::unchecked(TRADER),
+            fee_override: Some(FeeOverride::Custom {
+                pct: Uint128::new(3_000),
+            }),
+        });
+        execute_as(&mut deps, ADMIN, &[], msg).unwrap();
+        assert_ne!(
+            Quoter::new(&overview(&deps)).unwrap().fees.effective_taker_pct,
+            fee_preview(&deps, TRADER).taker_pct
+        );
+        assert_matches_contract(&mut deps, swaps());
+    }
+
+    #[test]
+    fn base_fee_matches_contract() {
+        let mut deps = setup_with(InstantiateMsg {
+            curve_kind: Some(CurveKind::ConstantProduct {}),
+            sellable_supply: Some(Uint128::new(800_000_000_000_000)),
+            taker_fee_asset: Some(FeeAsset::Base),
+            maker_fee_asset: Some(FeeAsset::Base),
+            ..instantiate_msg(30_000_000_000)
+        });
+        assert_matches_contract(&mut deps, swaps());
+        assert!(!overview(&deps).fees.recipients[0].total_paid_base.is_zero());
+    }
+
+    #[test]
+    fn partial_fill_matches_contract() {
+        let mut deps = setup(
+            CurveKind::Linear {
+                start_price: Decimal256::from_ratio(3u128, 100_000u128),
+                end_price: Decimal256::from_ratio(3u128, 1_000u128),
+            },
+            0,
+        );
+        assert_matches_contract(&mut deps, vec![Swap::Buy(1_000_000_000_000_000), Swap::Sell(5_000_000)]);
+    }
+}
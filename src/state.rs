@@ -2,17 +2,24 @@ use cw20::{Cw20Coin, Logo, MinterResponse};
 use cw20_base::msg::{InstantiateMarketingInfo, InstantiateMsg as Cw20InstantiateMsg};
 use cw_storage_plus::{Item, Map};
 
-use cosmwasm_std::{to_json_binary, Addr, DepsMut, Reply, Response, StdError, SubMsg, Uint128, WasmMsg};
+use cosmwasm_std::{
+    to_json_binary, Addr, DepsMut, Reply, Response, StdError, SubMsg, Timestamp, Uint128, Uint256, WasmMsg,
+};
 use cw_utils::{parse_reply_instantiate_data, MsgInstantiateContractResponse};
 
 use crate::{
-    curve::Curve,
+    curve::{Curve, CurveKind},
     error::ContractError,
     execute::Context,
-    math::mul_u256,
+    math::{add_u256, mul_u256},
     models::{
         account::{AccountStats, SwapStats},
+        fee::{
+            DynamicFeeConfig, FeeAsset, FeeOverride, FeeRecipient, HolderTierConfig, LaunchFeeSchedule, NetFee,
+            VolumeTier, MAX_FEE_RECIPIENTS,
+        },
         ohlc::OhlcBar,
+        referral::ReferrerStats,
     },
     msg::InstantiateMsg,
     token::Token,
@@ -23,9 +30,6 @@ pub const CW20_INSTANTIATE_REPLY_ID: u64 = 1;
 // Curve consists of reserve amounts & other vars used by CP AMM
 pub const CURVE: Item<Curve> = Item::new("curve");
 
-// Initial "virtual" quote reserve amount
-pub const QUOTE_RESERVE_VIRTUAL: Item<Uint128> = Item::new("virtual_quote_reserve");
-
 // Token addresses/denoms for base and quote tokens
 pub const QUOTE_TOKEN: Item<Token> = Item::new("q_token");
 pub const BASE_TOKEN: Item<Token> = Item::new("b_token");
@@ -35,10 +39,54 @@ pub const BASE_TOKEN: Item<Token> = Item::new("b_token");
 // other smart contract.
 pub const OPERATOR_ADDR: Item<Addr> = Item::new("operator_addr");
 
+// Party authorized to manage fee overrides
+pub const ADMIN_ADDR: Item<Addr> = Item::new("admin_addr");
+
 // Fee configuration
 pub const FEE_ADDR: Item<Addr> = Item::new("fee_addr");
 pub const FEE_PCT_BUY: Item<Uint128> = Item::new("b_fee");
 pub const FEE_PCT_SELL: Item<Uint128> = Item::new("s_fee");
+pub const FEE_ASSET_BUY: Item<FeeAsset> = Item::new("b_fee_asset");
+pub const FEE_ASSET_SELL: Item<FeeAsset> = Item::new("s_fee_asset");
+
+// Time of instantiation, and the optional schedule of elevated taker fees
+// charged for a while after it
+pub const LAUNCHED_AT: Item<Timestamp> = Item::new("launched_at");
+pub const LAUNCH_FEE: Item<LaunchFeeSchedule> = Item::new("launch_fee");
+
+// If defined, fees track recent volatility instead of the flat fee pcts
+pub const DYNAMIC_FEE: Item<DynamicFeeConfig> = Item::new("dynamic_fee");
+
+// Fees charged to specific swap initiators in place of the regular fees
+pub const FEE_OVERRIDES: Map<&Addr, FeeOverride> = Map::new("fee_overrides");
+
+// If defined, fee discounts for holders of the tier token
+pub const HOLDER_TIERS: Item<HolderTierConfig> = Item::new("holder_tiers");
+
+// If defined, fee discounts by rolling 30-day quote volume
+pub const VOLUME_TIERS: Item<Vec<VolumeTier>> = Item::new("volume_tiers");
+
+// Recipients that split each fee by weight, and the total paid to each so far
+pub const FEE_RECIPIENTS: Item<Vec<FeeRecipient>> = Item::new("fee_recipients");
+pub const FEE_RECIPIENT_TOTALS: Map<&Addr, Uint256> = Map::new("fee_recipient_totals");
+pub const FEE_RECIPIENT_BASE_TOTALS: Map<&Addr, Uint256> = Map::new("fee_recipient_base_totals");
+
+// Fees accrued to each fee recipient and referrer, withdrawn via ClaimFees
+pub const CLAIMABLE_FEES: Map<&Addr, Uint128> = Map::new("claimable_fees");
+
+// Portion of each fee retained in the curve's reserves rather than sent to the
+// fee recipient, and the total quote amount retained so far
+pub const LP_FEE_PCT: Item<Uint128> = Item::new("lp_fee");
+pub const LP_FEE_RETAINED: Item<Uint256> = Item::new("lp_fee_retained");
+
+// Share of each fee paid to the swap's referrer, the stats of each referrer,
+// and the set of (referrer, referee) pairs seen so far
+pub const REFERRAL_FEE_PCT: Item<Uint128> = Item::new("referral_fee");
+pub const REFERRER_STATS: Map<&Addr, ReferrerStats> = Map::new("referrer_stats");
+pub const REFEREES: Map<(&Addr, &Addr), bool> = Map::new("referees");
+
+// Constant product at instantiation, before any growth from retained fees
+pub const K_INITIAL: Item<Uint256> = Item::new("k_initial");
 
 // Historical price OHLC time series
 pub const OHLC_BARS: Map<u64, OhlcBar> = Map::new("ohlc_bars");
@@ -46,9 +94,12 @@ pub const OHLC_BARS: Map<u64, OhlcBar> = Map::new("ohlc_bars");
 // Account-level statistics
 pub const ACCOUNT_STATS: Map<&Addr, AccountStats> = Map::new("account_stats");
 
+// Quote volume traded by each account, bucketed by day since the epoch
+pub const ACCOUNT_DAILY_VOLUME: Map<(&Addr, u64), Uint256> = Map::new("account_daily_volume");
+
 // Global Statistic
-pub const NET_TAKER_FEE: Item<Uint128> = Item::new("net_taker_fee");
-pub const NET_MAKER_FEE: Item<Uint128> = Item::new("net_maker_fee");
+pub const NET_TAKER_FEE: Item<NetFee> = Item::new("net_taker_fee");
+pub const NET_MAKER_FEE: Item<NetFee> = Item::new("net_maker_fee");
 pub const TAKER_STATS: Item<SwapStats> = Item::new("taker_stats");
 pub const MAKER_STATS: Item<SwapStats> = Item::new("maker_stats");
 
@@ -57,40 +108,165 @@ pub fn init(
     ctx: Context,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
-    let Context { deps, env, .. } = ctx;
+    let Context { deps, env, info } = ctx;
     let InstantiateMsg {
         operator_addr,
+        admin_addr,
         base_token,
         base_reserve,
+        base_reserve_virtual,
         quote_reserve,
         quote_token,
+        targets,
+        curve_kind,
+        sellable_supply,
         taker_fee_pct,
         maker_fee_pct,
+        taker_fee_asset,
+        maker_fee_asset,
+        lp_fee_pct,
+        referral_fee_pct,
+        launch_fee,
+        dynamic_fee,
+        holder_tiers,
+        volume_tiers,
         fee_addr,
+        fee_recipients,
     } = msg;
 
+    let fee_addr = deps.api.addr_validate(fee_addr.as_str())?;
+    let fee_recipients = fee_recipients.unwrap_or(vec![FeeRecipient {
+        address: fee_addr.to_owned(),
+        weight: 1,
+    }]);
+
+    if fee_recipients.is_empty() || fee_recipients.len() > MAX_FEE_RECIPIENTS {
+        return Err(ContractError::ValidationError {
+            reason: "fee recipient count out of range".to_owned(),
+        });
+    }
+    for (i, recipient) in fee_recipients.iter().enumerate() {
+        deps.api.addr_validate(recipient.address.as_str())?;
+        if recipient.weight == 0 || fee_recipients[..i].iter().any(|r| r.address == recipient.address) {
+            return Err(ContractError::ValidationError {
+                reason: "fee recipients need a nonzero weight and a unique address".to_owned(),
+            });
+        }
+    }
+    if !fee_recipients.iter().any(|r| r.address == fee_addr) {
+        return Err(ContractError::ValidationError {
+            reason: "fee addr must be one of the fee recipients".to_owned(),
+        });
+    }
+
     QUOTE_TOKEN.save(deps.storage, &quote_token.token)?;
-    FEE_ADDR.save(deps.storage, &deps.api.addr_validate(fee_addr.as_str())?)?;
+    FEE_ADDR.save(deps.storage, &fee_addr)?;
+    FEE_RECIPIENTS.save(deps.storage, &fee_recipients)?;
     FEE_PCT_BUY.save(deps.storage, &taker_fee_pct.min(1_000_000u128.into()))?;
     FEE_PCT_SELL.save(deps.storage, &maker_fee_pct.min(1_000_000u128.into()))?;
-    NET_TAKER_FEE.save(deps.storage, &Uint128::zero())?;
-    NET_MAKER_FEE.save(deps.storage, &Uint128::zero())?;
-    QUOTE_RESERVE_VIRTUAL.save(deps.storage, &quote_reserve)?;
+    FEE_ASSET_BUY.save(deps.storage, &taker_fee_asset.unwrap_or_default())?;
+    FEE_ASSET_SELL.save(deps.storage, &maker_fee_asset.unwrap_or_default())?;
+    LP_FEE_PCT.save(deps.storage, &lp_fee_pct.unwrap_or_default().min(1_000_000u128.into()))?;
+    LP_FEE_RETAINED.save(deps.storage, &Uint256::zero())?;
+    REFERRAL_FEE_PCT.save(
+        deps.storage,
+        &referral_fee_pct.unwrap_or_default().min(1_000_000u128.into()),
+    )?;
+    LAUNCHED_AT.save(deps.storage, &env.block.time)?;
+    if let Some(launch_fee) = launch_fee {
+        launch_fee.validate()?;
+        LAUNCH_FEE.save(deps.storage, &launch_fee)?;
+    }
+    if let Some(dynamic_fee) = dynamic_fee {
+        dynamic_fee.validate()?;
+        DYNAMIC_FEE.save(deps.storage, &dynamic_fee)?;
+    }
+    if let Some(holder_tiers) = holder_tiers {
+        holder_tiers.validate(deps.as_ref(), &env.contract.address)?;
+        HOLDER_TIERS.save(deps.storage, &holder_tiers)?;
+    }
+    if let Some(volume_tiers) = volume_tiers {
+        VolumeTier::validate_tiers(&volume_tiers)?;
+        VOLUME_TIERS.save(deps.storage, &volume_tiers)?;
+    }
+    NET_TAKER_FEE.save(deps.storage, &NetFee::default())?;
+    NET_MAKER_FEE.save(deps.storage, &NetFee::default())?;
     TAKER_STATS.save(deps.storage, &SwapStats::default())?;
     MAKER_STATS.save(deps.storage, &SwapStats::default())?;
 
+    let kind = curve_kind.unwrap_or(CurveKind::ConstantProduct {});
+    kind.validate()?;
+
+    // Quote retained by any other kind of curve would be locked in for good
+    if !matches!(kind, CurveKind::ConstantProduct {}) && !lp_fee_pct.unwrap_or_default().is_zero() {
+        return Err(ContractError::ValidationError {
+            reason: "an LP fee only applies to a constant product curve".to_owned(),
+        });
+    }
+
+    // Derive the curve's reserves from launch targets, or take them as given.
+    // Whatever part of the curve's base reserve exceeds the minted base reserve
+    // is virtual.
+    let (curve_base_reserve, quote_reserve, sellable_supply) = match (targets, quote_reserve) {
+        (Some(targets), None) => {
+            if sellable_supply.is_some()
+                || base_reserve_virtual.is_some()
+                || !matches!(kind, CurveKind::ConstantProduct {})
+            {
+                return Err(ContractError::ValidationError {
+                    reason: "targets only apply to a constant product curve without a sellable supply or virtual base reserve".to_owned(),
+                });
+            }
+            targets.derive_reserves(base_reserve, base_token.decimals, quote_token.decimals)?
+        },
+        (None, Some(quote_reserve)) => {
+            let sellable_supply = sellable_supply.unwrap_or(base_reserve);
+            if sellable_supply.is_zero() || sellable_supply > base_reserve {
+                return Err(ContractError::ValidationError {
+                    reason: "sellable supply must be nonzero and within the base reserve".to_owned(),
+                });
+            }
+            (
+                add_u256(base_reserve, base_reserve_virtual.unwrap_or_default())?,
+                quote_reserve,
+                sellable_supply.into(),
+            )
+        },
+        _ => {
+            return Err(ContractError::ValidationError {
+                reason: "expected either a quote reserve or curve targets".to_owned(),
+            })
+        },
+    };
+
     if let Some(operator_addr) = operator_addr {
         OPERATOR_ADDR.save(deps.storage, &deps.api.addr_validate(operator_addr.as_str())?)?;
     }
 
+    ADMIN_ADDR.save(
+        deps.storage,
+        &match admin_addr {
+            Some(admin_addr) => deps.api.addr_validate(admin_addr.as_str())?,
+            None => info.sender,
+        },
+    )?;
+
+    let k = mul_u256(curve_base_reserve, quote_reserve)?;
+
+    K_INITIAL.save(deps.storage, &k)?;
     CURVE.save(
         deps.storage,
         &Curve {
-            k: mul_u256(base_reserve, quote_reserve)?,
+            kind,
+            k,
+            base_supply: curve_base_reserve,
+            sellable_supply,
             base_decimals: base_token.decimals,
             quote_decimals: quote_token.decimals,
-            base_reserve,
+            base_reserve: curve_base_reserve,
+            base_reserve_virtual: curve_base_reserve.saturating_sub(base_reserve.into()),
             quote_reserve,
+            quote_reserve_virtual: quote_reserve,
         },
     )?;
 
@@ -137,3 +313,39 @@ pub fn handle_cw20_instantiate_reply(
 
     Ok(())
 }
+
+#[cfg(all(test, not(feature = "library")))]
+mod tests {
+    use cosmwasm_std::{
+        testing::{mock_dependencies, mock_env, mock_info},
+        Decimal256,
+    };
+
+    use super::*;
+    use crate::{contract::instantiate, testing::instantiate_msg};
+
+    #[test]
+    fn rejects_lp_fee_unless_constant_product() {
+        let msg = InstantiateMsg {
+            curve_kind: Some(CurveKind::Linear {
+                start_price: Decimal256::from_ratio(3u128, 100_000u128),
+                end_price: Decimal256::from_ratio(3u128, 1_000u128),
+            }),
+            quote_reserve: Some(Uint256::zero()),
+            ..instantiate_msg(0)
+        };
+        let result = instantiate(
+            mock_dependencies().as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            msg.clone(),
+        );
+        assert!(matches!(result, Err(ContractError::ValidationError { .. })));
+
+        let msg = InstantiateMsg {
+            lp_fee_pct: Some(Uint128::zero()),
+            ..msg
+        };
+        instantiate(mock_dependencies().as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    }
+}
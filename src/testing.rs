@@ -0,0 +1,237 @@
+//! Harness for tests that run messages through the contract's entry points
+
+use cosmwasm_std::{
+    coins, from_json,
+    testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage},
+    to_json_binary, Addr, Coin, OwnedDeps, Response, Uint128, Uint256, Uint64,
+};
+use cw20::Cw20ReceiveMsg;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    contract::{execute, instantiate, query},
+    curve::CurveKind,
+    error::ContractError,
+    models::fee::FeeRecipient,
+    msg::{
+        BaseTokenInitArgs, BuyMsg, ClaimableFeesResponse, Cw20ReceiveInnerMsg, ExecuteMsg, FeePreviewResponse,
+        InstantiateMsg, OverviewResponse, QueryMsg, QuoteTokenInitArgs, SellMsg,
+    },
+    state::BASE_TOKEN,
+    token::Token,
+};
+
+pub type TestDeps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+/// Instantiator of the contract, which is also its admin
+pub const ADMIN: &str = "creator";
+pub const TRADER: &str = "trader";
+/// Address of the base token's cw20 contract
+pub const BASE: &str = "base";
+pub const QUOTE_DENOM: &str = "uquote";
+
+/// Curve over 1B base with a 1% taker fee and a 2% maker fee, selling 80% of
+/// its supply. A constant product curve also retains a 25% LP fee.
+pub fn setup(
+    curve_kind: CurveKind,
+    quote_reserve: u128,
+) -> TestDeps {
+    setup_with(InstantiateMsg {
+        lp_fee_pct: match curve_kind {
+            CurveKind::ConstantProduct {} => Some(Uint128::new(250_000)),
+            _ => None,
+        },
+        curve_kind: Some(curve_kind),
+        sellable_supply: Some(Uint128::new(800_000_000_000_000)),
+        ..instantiate_msg(quote_reserve)
+    })
+}
+
+/// Instantiate msg that leaves the curve shape and sellable supply to their
+/// defaults, i.e. a constant product curve selling its whole base reserve
+pub fn instantiate_msg(quote_reserve: u128) -> InstantiateMsg {
+    InstantiateMsg {
+        operator_addr: None,
+        admin_addr: None,
+        base_token: BaseTokenInitArgs {
+            code_id: Uint64::one(),
+            symbol: "BASE".to_owned(),
+            decimals: 6,
+            name: "Base".to_owned(),
+            image_url: None,
+            description: None,
+            project_url: None,
+            cap: None,
+        },
+        base_reserve: Uint128::new(1_000_000_000_000_000),
+        base_reserve_virtual: None,
+        quote_token: QuoteTokenInitArgs {
+            token: Token::Denom(QUOTE_DENOM.to_owned()),
+            decimals: 6,
+        },
+        quote_reserve: Some(Uint256::from(quote_reserve)),
+        targets: None,
+        curve_kind: None,
+        sellable_supply: None,
+        taker_fee_pct: Uint128::new(10_000),
+        maker_fee_pct: Uint128::new(20_000),
+        lp_fee_pct: Some(Uint128::new(250_000)),
+        referral_fee_pct: None,
+        launch_fee: None,
+        dynamic_fee: None,
+        holder_tiers: None,
+        volume_tiers: None,
+        taker_fee_asset: None,
+        maker_fee_asset: None,
+        fee_addr: Addr::unchecked("fees"),
+        fee_recipients: None,
+    }
+}
+
+/// Instantiates the contract, standing in for the reply that records the
+/// base token's address
+pub fn setup_with(msg: InstantiateMsg) -> TestDeps {
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env(), mock_info(ADMIN, &[]), msg).unwrap();
+    BASE_TOKEN
+        .save(deps.as_mut().storage, &Token::Address(Addr::unchecked(BASE)))
+        .unwrap();
+    deps
+}
+
+/// Default curve whose fees go to "fees" and "dev" with weights of 1 and 2,
+/// with no LP fee
+pub fn setup_fee_split() -> TestDeps {
+    setup_with(InstantiateMsg {
+        lp_fee_pct: None,
+        fee_recipients: Some(vec![
+            FeeRecipient {
+                address: Addr::unchecked("fees"),
+                weight: 1,
+            },
+            FeeRecipient {
+                address: Addr::unchecked("dev"),
+                weight: 2,
+            },
+        ]),
+        ..instantiate_msg(30_000_000_000)
+    })
+}
+
+pub fn execute_as(
+    deps: &mut TestDeps,
+    sender: &str,
+    funds: &[Coin],
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    execute(deps.as_mut(), mock_env(), mock_info(sender, funds), msg)
+}
+
+pub fn query_as<T: DeserializeOwned>(
+    deps: &TestDeps,
+    msg: QueryMsg,
+) -> T {
+    from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+}
+
+pub fn overview(deps: &TestDeps) -> OverviewResponse {
+    query_as(deps, QueryMsg::Overview {})
+}
+
+pub fn fee_preview(
+    deps: &TestDeps,
+    address: &str,
+) -> FeePreviewResponse {
+    query_as(
+        deps,
+        QueryMsg::FeePreview {
+            address: Addr::unchecked(address),
+        },
+    )
+}
+
+/// Value of a numeric response attribute, or zero if absent
+pub fn attr(
+    resp: &Response,
+    key: &str,
+) -> Uint128 {
+    resp.attributes
+        .iter()
+        .find(|a| a.key == key)
+        .map(|a| a.value.parse().unwrap())
+        .unwrap_or_default()
+}
+
+pub fn buy_msg() -> BuyMsg {
+    BuyMsg {
+        initiator: None,
+        referrer: None,
+        min_out_amount: None,
+        max_price_impact_bps: None,
+    }
+}
+
+pub fn sell_msg() -> SellMsg {
+    SellMsg {
+        initiator: None,
+        referrer: None,
+        min_out_amount: None,
+        max_price_impact_bps: None,
+    }
+}
+
+/// Buys with the given quote amount as the trader
+pub fn buy(
+    deps: &mut TestDeps,
+    amount: u128,
+) -> Response {
+    execute_as(deps, TRADER, &coins(amount, QUOTE_DENOM), ExecuteMsg::Buy(buy_msg())).unwrap()
+}
+
+/// Sells the given base amount as the trader
+pub fn sell(
+    deps: &mut TestDeps,
+    amount: u128,
+) -> Response {
+    receive(deps, TRADER, amount, Cw20ReceiveInnerMsg::Sell(sell_msg())).unwrap()
+}
+
+/// Sends base tokens to the contract along with the given msg
+pub fn receive(
+    deps: &mut TestDeps,
+    sender: &str,
+    amount: u128,
+    msg: Cw20ReceiveInnerMsg,
+) -> Result<Response, ContractError> {
+    execute_as(
+        deps,
+        BASE,
+        &[],
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: sender.to_owned(),
+            amount: Uint128::new(amount),
+            msg: to_json_binary(&msg).unwrap(),
+        }),
+    )
+}
+
+/// Quote fees accrued to the address and not yet claimed
+pub fn claimable(
+    deps: &TestDeps,
+    address: &str,
+) -> Uint128 {
+    let msg = QueryMsg::ClaimableFees {
+        address: Addr::unchecked(address),
+    };
+    query_as::<ClaimableFeesResponse>(deps, msg).amount
+}
+
+/// Total quote fees paid to each fee recipient so far
+pub fn total_paid(deps: &TestDeps) -> Vec<(String, Uint256)> {
+    overview(deps)
+        .fees
+        .recipients
+        .into_iter()
+        .map(|r| (r.address.to_string(), r.total_paid))
+        .collect()
+}
@@ -1,6 +1,155 @@
-use cosmwasm_std::{Addr, Api, Storage};
+use cosmwasm_std::{ensure_eq, Addr, Api, Deps, Storage, Timestamp, Uint128};
 
-use crate::{error::ContractError, state::OPERATOR_ADDR};
+use crate::{
+    error::ContractError,
+    math::{mul_pct_u128, sub_u128},
+    models::{
+        account::calculate_rolling_volume,
+        fee::{HolderTier, VolumeTier},
+        ohlc::OhlcBar,
+    },
+    state::{
+        ADMIN_ADDR, BASE_TOKEN, DYNAMIC_FEE, FEE_OVERRIDES, FEE_PCT_BUY, FEE_PCT_SELL, HOLDER_TIERS, LAUNCHED_AT,
+        LAUNCH_FEE, OPERATOR_ADDR, VOLUME_TIERS,
+    },
+};
+
+/// Taker fee pct in effect at the given time, which is elevated by the launch
+/// fee schedule, if any, for a while after instantiation. An initiator with a
+/// fee override pays its own pct instead. Otherwise, the initiator gets the
+/// better of its holder and volume tier discounts.
+pub fn resolve_buy_fee_pct(
+    deps: Deps,
+    time: Timestamp,
+    initiator: Option<&Addr>,
+) -> Result<Uint128, ContractError> {
+    let store = deps.storage;
+    if let Some(fee_pct) = resolve_fee_override_pct(store, initiator)? {
+        return Ok(fee_pct);
+    }
+    let mut fee_pct = match resolve_dynamic_fee_pct(store, time)? {
+        Some(fee_pct) => fee_pct,
+        None => FEE_PCT_BUY.load(store)?,
+    };
+    if let Some(launch_fee) = LAUNCH_FEE.may_load(store)? {
+        let elapsed = time.seconds().saturating_sub(LAUNCHED_AT.load(store)?.seconds());
+        fee_pct = launch_fee.calculate_fee_pct(fee_pct, elapsed)?;
+    }
+    apply_discount(fee_pct, resolve_discount_pct(deps, time, initiator)?)
+}
+
+/// Maker fee pct in effect at the given time, subject to the initiator's fee
+/// override or tier discounts
+pub fn resolve_sell_fee_pct(
+    deps: Deps,
+    time: Timestamp,
+    initiator: Option<&Addr>,
+) -> Result<Uint128, ContractError> {
+    let store = deps.storage;
+    if let Some(fee_pct) = resolve_fee_override_pct(store, initiator)? {
+        return Ok(fee_pct);
+    }
+    let fee_pct = match resolve_dynamic_fee_pct(store, time)? {
+        Some(fee_pct) => fee_pct,
+        None => FEE_PCT_SELL.load(store)?,
+    };
+    apply_discount(fee_pct, resolve_discount_pct(deps, time, initiator)?)
+}
+
+/// Highest holder tier whose min balance the initiator's balance of the tier
+/// token meets, if any
+pub fn resolve_holder_tier(
+    deps: Deps,
+    initiator: Option<&Addr>,
+) -> Result<Option<HolderTier>, ContractError> {
+    if let (Some(initiator), Some(config)) = (initiator, HOLDER_TIERS.may_load(deps.storage)?) {
+        let token = match config.token {
+            Some(token) => token,
+            None => BASE_TOKEN.load(deps.storage)?,
+        };
+        let balance = token.query_balance(deps.querier, initiator)?;
+        return Ok(config.tiers.into_iter().rev().find(|tier| balance >= tier.min_balance));
+    }
+    Ok(None)
+}
+
+/// Highest volume tier whose min volume the initiator's rolling volume
+/// meets, if any
+pub fn resolve_volume_tier(
+    store: &dyn Storage,
+    time: Timestamp,
+    initiator: Option<&Addr>,
+) -> Result<Option<VolumeTier>, ContractError> {
+    if let (Some(initiator), Some(tiers)) = (initiator, VOLUME_TIERS.may_load(store)?) {
+        let volume = calculate_rolling_volume(store, initiator, time)?;
+        return Ok(tiers.into_iter().rev().find(|tier| volume >= tier.min_volume));
+    }
+    Ok(None)
+}
+
+/// Larger of the initiator's holder and volume tier discounts
+fn resolve_discount_pct(
+    deps: Deps,
+    time: Timestamp,
+    initiator: Option<&Addr>,
+) -> Result<Uint128, ContractError> {
+    let holder_discount = resolve_holder_tier(deps, initiator)?
+        .map(|t| t.discount_pct)
+        .unwrap_or_default();
+    let volume_discount = resolve_volume_tier(deps.storage, time, initiator)?
+        .map(|t| t.discount_pct)
+        .unwrap_or_default();
+    Ok(holder_discount.max(volume_discount))
+}
+
+fn apply_discount(
+    fee_pct: Uint128,
+    discount_pct: Uint128,
+) -> Result<Uint128, ContractError> {
+    sub_u128(fee_pct, mul_pct_u128(fee_pct, discount_pct)?)
+}
+
+/// Fee pct set by the admin for the initiator, if any
+fn resolve_fee_override_pct(
+    store: &dyn Storage,
+    initiator: Option<&Addr>,
+) -> Result<Option<Uint128>, ContractError> {
+    if let Some(initiator) = initiator {
+        return Ok(FEE_OVERRIDES.may_load(store, initiator)?.map(|o| o.fee_pct()));
+    }
+    Ok(None)
+}
+
+/// Fee pct set by recent volatility, if dynamic fees are enabled
+fn resolve_dynamic_fee_pct(
+    store: &dyn Storage,
+    time: Timestamp,
+) -> Result<Option<Uint128>, ContractError> {
+    if let Some(dynamic_fee) = DYNAMIC_FEE.may_load(store)? {
+        let since = time.minus_seconds(dynamic_fee.window_minutes as u64 * 60);
+        return Ok(Some(
+            dynamic_fee.calculate_fee_pct(OhlcBar::calculate_range_bps(store, since)?)?,
+        ));
+    }
+    Ok(None)
+}
+
+/// Fails unless the sender is the admin
+pub fn ensure_admin(
+    store: &dyn Storage,
+    sender: &Addr,
+    action: &str,
+) -> Result<(), ContractError> {
+    let admin_addr = ADMIN_ADDR.load(store)?;
+    ensure_eq!(
+        *sender,
+        admin_addr,
+        ContractError::NotAuthorized {
+            reason: format!("only admin {} is authorized to {}", admin_addr, action)
+        }
+    );
+    Ok(())
+}
 
 // If an operator address exists, then ensure that the only authorized sender is
 // the operator itself. The operator is indended to be the address of some other